@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{load_json, save_json};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Bookmark {
+    pub title: String,
+    pub uri: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Bookmarks {
+    pub bookmarks: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    pub fn save(&self) {
+        save_json("bookmarks.json", self);
+    }
+
+    pub fn load() -> Bookmarks {
+        load_json("bookmarks.json")
+    }
+
+    /// Adds a bookmark unless the uri is already bookmarked.
+    pub fn add(&mut self, title: &str, uri: &str) {
+        if self.bookmarks.iter().any(|bookmark| bookmark.uri == uri) {
+            return;
+        }
+
+        self.bookmarks.push(Bookmark {
+            title: title.to_string(),
+            uri: uri.to_string(),
+        });
+    }
+}
@@ -1,24 +1,36 @@
 extern crate gtk;
 extern crate webkit2gtk;
 
-use gtk::{prelude::*, Box, Button, Entry, Label, Notebook, Popover, Switch};
+use gtk::{prelude::*, Box, Button, Entry, EntryIconPosition, Label, Notebook, Popover, Switch};
 use std::cell::RefCell;
 use std::rc::Rc;
 use url::Url;
 use webkit2gtk::{WebView, WebViewExt};
 
-use crate::create_window;
+use crate::findbar::sync_find_bar;
+use crate::groups::refresh_group_bar;
+use crate::keybindings::{run_action, shortcut_label, Action};
+use crate::reader::{sync_reader_icon, toggle_reader_mode};
+use crate::security::{show_security_popover, sync_security_icon};
+use crate::session::save_session;
 use crate::settings::toggle_adblock;
+use crate::sidebar::{refresh_tab_sidebar, toggle_vertical_tabs};
+use crate::sitesettings::sync_site_switches;
 use crate::styles::apply_css_style;
-use crate::webview::{change_webview_setting, get_webview_setting, WebviewSetting};
+use crate::tabs::{
+    close_window_if_empty, is_tab_pinned, page_webview, pinned_tab_count, resume_tab, touch_tab,
+};
+use crate::vim::{close_prompt, take_prompt, Prompt};
+use crate::zoom::{reset_zoom, update_zoom_indicator};
+use crate::{create_empty_window, create_window, find_browser_window};
 use crate::{settings::show_settings_window, tabs::add_tab};
 
 pub fn get_webview(notebook: &Notebook) -> Option<WebView> {
     let current_page = notebook.current_page();
 
     if let Some(widget) = notebook.nth_page(current_page) {
-        if let Some(webview) = widget.downcast_ref::<WebView>() {
-            return Some(webview.clone());
+        if let Some(webview) = page_webview(&widget) {
+            return Some(webview);
         }
     }
 
@@ -71,12 +83,42 @@ pub fn refresh_button_clicked(notebook: &Notebook, refresh_button: &Button) {
     });
 }
 
+pub fn zoom_button_clicked(notebook: &Notebook, zoom_button: &Button) {
+    zoom_button.connect_clicked({
+        let notebook = notebook.clone();
+        move |_| reset_zoom(&notebook)
+    });
+}
+
+pub fn address_bar_icon_pressed(notebook: &Notebook, search_entry: &Entry) {
+    search_entry.connect_icon_press({
+        let notebook = notebook.clone();
+        move |search_entry, position, _| {
+            let browser_window = match find_browser_window(&notebook) {
+                Some(browser_window) => browser_window,
+                None => return,
+            };
+
+            match position {
+                EntryIconPosition::Primary => {
+                    if let Some(page) = notebook.nth_page(notebook.current_page()) {
+                        show_security_popover(search_entry, &page);
+                    }
+                }
+                _ => toggle_reader_mode(&browser_window),
+            }
+        }
+    });
+}
+
 pub fn new_tab_button_clicked(notebook: &Notebook, new_tab_button: &Button, search_entry: &Entry) {
     new_tab_button.connect_clicked({
         let notebook = notebook.clone();
         let search_entry = search_entry.clone();
 
-        move |_| add_tab(&notebook, &search_entry, None)
+        move |_| {
+            add_tab(&notebook, &search_entry, None);
+        }
     });
 }
 
@@ -91,147 +133,146 @@ pub fn search_entry_activate(search_entry: &Entry, notebook: &Notebook) {
                 return;
             }
 
-            match get_webview(&notebook) {
-                Some(webview) => {
-                    let url_str = url.as_str();
-
-                    if let Ok(url) = Url::parse(url_str) {
-                        if url.scheme() == "http" || url.scheme() == "https" {
-                            if url.host_str() == Some("localhost") || url.path() == "/" {
-                                println!("Local URL detected!");
-                                webview.load_uri(&url_str);
-                                return;
-                            }
-                        } else if url.scheme() == "file" {
-                            println!("File URL detected!");
-                            webview.load_uri(&url_str);
-                            return;
-                        }
-
-                        webview.load_uri(&url_str);
-
-                        return;
-                    }
-
-                    let domain_like = url_str.contains('.') && !url_str.contains(' ');
-
-                    if domain_like {
-                        println!("URL detected (no scheme)!");
-                        webview.load_uri(&format!("https://{}", &url_str));
-
-                        return;
-                    }
-
-                    println!("Search query detected");
-                    let search_query = url.to_string().replace(" ", "+");
-                    webview.load_uri(&format!("https://duckduckgo.com/?q={}", &search_query));
+            match take_prompt() {
+                Some(Prompt::TabOpen) => {
+                    close_prompt(search_entry, &notebook);
+                    add_tab(&notebook, search_entry, Some(&input_to_uri(&url)));
                     return;
                 }
+                Some(Prompt::Open) => search_entry.set_placeholder_text(None),
+                None => {}
+            }
+
+            match get_webview(&notebook) {
+                Some(webview) => webview.load_uri(&input_to_uri(&url)),
                 None => println!("Current tab doesn't have a webview"),
             }
         }
     });
 }
 
-pub fn notebook_switch_page(notebook: &Notebook, search_entry: &Entry, menu_popup_box: Box) {
+/// Turns what was typed in the address bar into a URI, searching for anything
+/// that doesn't look like an address.
+pub fn input_to_uri(input: &str) -> String {
+    if let Ok(url) = Url::parse(input) {
+        if url.scheme() == "http" || url.scheme() == "https" {
+            if url.host_str() == Some("localhost") || url.path() == "/" {
+                println!("Local URL detected!");
+            }
+        } else if url.scheme() == "file" {
+            println!("File URL detected!");
+        }
+
+        return input.to_string();
+    }
+
+    let domain_like = input.contains('.') && !input.contains(' ');
+
+    if domain_like {
+        println!("URL detected (no scheme)!");
+        return format!("https://{}", input);
+    }
+
+    println!("Search query detected");
+    search_uri(input)
+}
+
+/// Search results page for `query` on the default search engine.
+pub fn search_uri(query: &str) -> String {
+    let search_query = query.replace(" ", "+");
+    format!("https://duckduckgo.com/?q={}", &search_query)
+}
+
+pub fn notebook_switch_page(notebook: &Notebook, search_entry: &Entry) {
     notebook.connect_switch_page({
         let search_entry = search_entry.clone();
-        let menu_popup_box = menu_popup_box.clone();
 
         move |notebook, _, page_num| {
             if let Some(widget) = notebook.nth_page(Some(page_num)) {
-                if let Some(webview) = widget.downcast_ref::<webkit2gtk::WebView>() {
+                // Suspended tabs get their webview back as soon as they are selected
+                resume_tab(&widget);
+                touch_tab(&widget);
+                refresh_tab_sidebar(notebook);
+
+                if let Some(browser_window) = find_browser_window(notebook) {
+                    sync_find_bar(&browser_window.find_bar, &widget);
+                    sync_reader_icon(&browser_window.search_bar, &widget);
+                    sync_security_icon(&browser_window.search_bar, &widget);
+                    update_zoom_indicator(
+                        &browser_window.zoom_button,
+                        page_webview(&widget).as_ref(),
+                    );
+                    sync_site_switches(
+                        &browser_window.site_switches,
+                        page_webview(&widget).as_ref(),
+                    );
+                }
+
+                if let Some(webview) = page_webview(&widget) {
                     if let Some(uri) = webview.uri() {
                         search_entry.set_text(&uri);
                     }
-
-                    for child in menu_popup_box.children() {
-                        if let Some(sub_box) = child.downcast_ref::<Box>() {
-                            for sub_child in sub_box.children() {
-                                if let Some(label) = sub_child.downcast_ref::<Label>() {
-                                    let label_text = label.text();
-
-                                    if label_text == "Javascript enabled" {
-                                        for sub_sub_child in sub_box.children() {
-                                            if let Some(_switch) =
-                                                sub_sub_child.downcast_ref::<Switch>()
-                                            {
-                                                let toggle_state = get_webview_setting(
-                                                    &webview,
-                                                    WebviewSetting::Javascript,
-                                                )
-                                                .expect("Failed to get setting");
-
-                                                change_webview_setting(
-                                                    &webview,
-                                                    WebviewSetting::Javascript,
-                                                    toggle_state,
-                                                );
-
-                                                // now i would also need to set the state of the switch
-
-                                                break;
-                                            }
-                                        }
-                                    }
-
-                                    if label_text == "WebGL enabled" {
-                                        for sub_sub_child in sub_box.children() {
-                                            if let Some(_switch) =
-                                                sub_sub_child.downcast_ref::<Switch>()
-                                            {
-                                                let toggle_state = get_webview_setting(
-                                                    &webview,
-                                                    WebviewSetting::WebGL,
-                                                )
-                                                .expect("Failed to get setting");
-
-                                                change_webview_setting(
-                                                    &webview,
-                                                    WebviewSetting::WebGL,
-                                                    toggle_state,
-                                                );
-
-                                                // now i would also need to set the state of the switch
-
-                                                break;
-                                            }
-                                        }
-                                    }
-
-                                    if label_text == "Auto Media Playback enabled" {
-                                        for sub_sub_child in sub_box.children() {
-                                            if let Some(_switch) =
-                                                sub_sub_child.downcast_ref::<Switch>()
-                                            {
-                                                let toggle_state = get_webview_setting(
-                                                    &webview,
-                                                    WebviewSetting::JsClipboardAccess,
-                                                )
-                                                .expect("Failed to get setting");
-
-                                                change_webview_setting(
-                                                    &webview,
-                                                    WebviewSetting::JsClipboardAccess,
-                                                    toggle_state,
-                                                );
-
-                                                // now i would also need to set the state of the switch
-
-                                                break;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
                 }
             }
         }
     });
 }
 
+pub fn notebook_page_added(notebook: &Notebook) {
+    // Tab properties don't survive a move between notebooks, so they are
+    // applied here for new tabs and tabs dragged in from another window alike
+    notebook.connect_page_added(|notebook, page, _| {
+        let pinned = is_tab_pinned(page);
+
+        notebook.set_tab_reorderable(page, !pinned);
+        notebook.set_tab_detachable(page, !pinned);
+
+        refresh_group_bar(notebook);
+        refresh_tab_sidebar(notebook);
+    });
+
+    notebook.connect_page_removed(|notebook, _, _| {
+        refresh_group_bar(notebook);
+        refresh_tab_sidebar(notebook);
+    });
+}
+
+pub fn notebook_create_window(notebook: &Notebook) {
+    // Dropping a tab outside of any window moves it into a new one, webview and all
+    notebook.connect_create_window(|_, _, x, y| {
+        let browser_window = create_empty_window();
+        browser_window.window.move_(x, y);
+
+        browser_window.notebook
+    });
+
+    // The tab has moved by the time the drag ends, whether into a new window
+    // or another one
+    notebook.connect_drag_end(|notebook, _| {
+        let notebook = notebook.clone();
+        gtk::glib::idle_add_local_once(move || close_window_if_empty(&notebook));
+    });
+}
+
+pub fn notebook_page_reordered(notebook: &Notebook) {
+    notebook.connect_page_reordered(|notebook, page, page_num| {
+        // Unpinned tabs can't be dragged in between the pinned ones
+        let pinned_count = pinned_tab_count(notebook);
+
+        if !is_tab_pinned(page) && page_num < pinned_count {
+            notebook.reorder_child(page, Some(pinned_count));
+        }
+
+        refresh_tab_sidebar(notebook);
+    });
+}
+
+pub fn notebook_session_tracking(notebook: &Notebook) {
+    notebook.connect_page_added(|_, _, _| save_session());
+    notebook.connect_page_removed(|_, _, _| save_session());
+    notebook.connect_page_reordered(|_, _, _| save_session());
+}
+
 pub fn settings_button_clicked(
     settings_button: &Button,
     notebook: &Notebook,
@@ -249,7 +290,7 @@ pub fn settings_button_clicked(
 
         let popup_box = Box::new(gtk::Orientation::Vertical, 0);
 
-        create_button_with_callback("New tab", &popup_box, "F1", {
+        create_button_with_callback("New tab", &popup_box, &shortcut_label(Action::NewTab), {
             let notebook = notebook.clone();
             let search_entry = search_entry.clone();
 
@@ -258,16 +299,42 @@ pub fn settings_button_clicked(
             }
         });
 
-        create_button_with_callback("New window", &popup_box, "F2", {
-            move || {
-                create_window(None);
-            }
-        });
+        create_button_with_callback(
+            "New window",
+            &popup_box,
+            &shortcut_label(Action::NewWindow),
+            {
+                move || {
+                    create_window(None);
+                }
+            },
+        );
+
+        for action in [Action::Print, Action::SaveAsPdf] {
+            create_button_with_callback(action.title(), &popup_box, &shortcut_label(action), {
+                let notebook = notebook.clone();
 
-        create_button_with_callback("Settings", &popup_box, "F12", || {
-            show_settings_window();
+                move || {
+                    if let Some(browser_window) = find_browser_window(&notebook) {
+                        run_action(action, &browser_window);
+                    }
+                }
+            });
+        }
+
+        create_button_with_callback("Vertical tabs", &popup_box, "", || {
+            toggle_vertical_tabs();
         });
 
+        create_button_with_callback(
+            "Settings",
+            &popup_box,
+            &shortcut_label(Action::Settings),
+            || {
+                show_settings_window();
+            },
+        );
+
         popup.add(&popup_box);
 
         popup.popup();
@@ -0,0 +1,92 @@
+use gtk::Widget;
+use std::rc::Rc;
+use webkit2gtk::{SettingsExt, WebInspectorExt, WebView, WebViewExt};
+
+use crate::settings::Settings;
+use crate::tabs::{all_webviews, page_webview, tab_state, TabState};
+
+/// Opens the web inspector for `page`, or closes it if it is already open.
+/// It comes up docked or detached, whichever the user used last.
+pub fn toggle_devtools(page: &Widget) {
+    if Settings::load().disable_developer_extras {
+        return;
+    }
+
+    let (state, inspector) = match (
+        tab_state(page),
+        page_webview(page).and_then(|webview| webview.inspector()),
+    ) {
+        (Some(state), Some(inspector)) => (state, inspector),
+        _ => return,
+    };
+
+    if state.devtools_open.get() {
+        inspector.close();
+        return;
+    }
+
+    inspector.show();
+}
+
+/// Turns the developer tools off or back on in every open tab.
+pub fn set_devtools_disabled(disabled: bool) {
+    for webview in all_webviews() {
+        if let Some(settings) = WebViewExt::settings(&webview) {
+            settings.set_enable_developer_extras(!disabled);
+        }
+
+        if disabled {
+            if let Some(inspector) = webview.inspector() {
+                inspector.close();
+            }
+        }
+    }
+}
+
+/// Tracks whether the inspector of `webview` is open, also when it was opened
+/// from the context menu, and remembers where it was docked.
+pub fn connect_inspector(webview: &WebView, state: Rc<TabState>) {
+    let inspector = match webview.inspector() {
+        Some(inspector) => inspector,
+        None => return,
+    };
+
+    // The inspector attaches when docked and opens a window when detached,
+    // both when it first shows and when the user moves it
+    inspector.connect_attach({
+        let state = state.clone();
+        move |inspector| {
+            // Showing docked while a window was used last: keep WebKit from
+            // docking it and move it into a window right away
+            if !state.devtools_open.get() && Settings::load().devtools_detached {
+                let inspector = inspector.clone();
+                gtk::glib::idle_add_local_once(move || inspector.detach());
+                return true;
+            }
+
+            state.devtools_open.set(true);
+            remember_detached(false);
+            false
+        }
+    });
+
+    inspector.connect_open_window({
+        let state = state.clone();
+        move |_| {
+            state.devtools_open.set(true);
+            remember_detached(true);
+            false
+        }
+    });
+
+    inspector.connect_closed(move |_| state.devtools_open.set(false));
+}
+
+fn remember_detached(detached: bool) {
+    let mut settings = Settings::load();
+
+    if settings.devtools_detached != detached {
+        settings.devtools_detached = detached;
+        settings.save();
+    }
+}
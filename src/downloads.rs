@@ -0,0 +1,59 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use webkit2gtk::{Download, DownloadExt, URIRequestExt};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DownloadStatus {
+    InProgress,
+    Finished,
+    Failed,
+}
+
+/// A download started during this session.
+pub struct DownloadRecord {
+    pub uri: String,
+    pub download: Download,
+    pub status: Rc<Cell<DownloadStatus>>,
+}
+
+thread_local! {
+    static DOWNLOADS: RefCell<Vec<DownloadRecord>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Keeps track of `download` so it shows up on abrw://downloads.
+pub fn track_download(download: &Download) {
+    let status = Rc::new(Cell::new(DownloadStatus::InProgress));
+
+    // "finished" follows "failed" too, so a failure is not overwritten
+    download.connect_failed({
+        let status = status.clone();
+        move |_, _| status.set(DownloadStatus::Failed)
+    });
+
+    download.connect_finished({
+        let status = status.clone();
+        move |_| {
+            if status.get() == DownloadStatus::InProgress {
+                status.set(DownloadStatus::Finished);
+            }
+        }
+    });
+
+    let uri = download
+        .request()
+        .and_then(|request| request.uri())
+        .map(|uri| uri.to_string())
+        .unwrap_or_default();
+
+    DOWNLOADS.with(|downloads| {
+        downloads.borrow_mut().push(DownloadRecord {
+            uri,
+            download: download.clone(),
+            status,
+        })
+    });
+}
+
+pub fn with_downloads<R>(f: impl FnOnce(&[DownloadRecord]) -> R) -> R {
+    DOWNLOADS.with(|downloads| f(&downloads.borrow()))
+}
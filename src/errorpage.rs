@@ -0,0 +1,322 @@
+use gtk::gio::{IOErrorEnum, NetworkMonitor, ResolverError};
+use gtk::prelude::*;
+use url::Url;
+use webkit2gtk::{NetworkError, PolicyError, WebView, WebViewExt};
+
+use crate::connections::search_uri;
+use crate::reader::escape_html;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadErrorKind {
+    Dns,
+    ConnectionRefused,
+    Timeout,
+    Offline,
+    TooManyRedirects,
+    BlockedByContentFilter,
+    UnsupportedScheme,
+    Other,
+}
+
+impl LoadErrorKind {
+    fn title(&self) -> &'static str {
+        match self {
+            LoadErrorKind::Dns => "Server not found",
+            LoadErrorKind::ConnectionRefused => "Connection refused",
+            LoadErrorKind::Timeout => "The connection timed out",
+            LoadErrorKind::Offline => "You are offline",
+            LoadErrorKind::TooManyRedirects => "Too many redirects",
+            LoadErrorKind::BlockedByContentFilter => "Blocked by the content filter",
+            LoadErrorKind::UnsupportedScheme => "Unsupported address",
+            LoadErrorKind::Other => "The page could not be loaded",
+        }
+    }
+
+    fn description(&self, host: &str) -> String {
+        match self {
+            LoadErrorKind::Dns => format!(
+                "No server could be found for <strong>{}</strong>. Check the address for typos.",
+                host
+            ),
+            LoadErrorKind::ConnectionRefused => format!(
+                "<strong>{}</strong> refused the connection. The site may be down or not accept connections on this port.",
+                host
+            ),
+            LoadErrorKind::Timeout => format!(
+                "<strong>{}</strong> took too long to respond. It may be busy or unreachable.",
+                host
+            ),
+            LoadErrorKind::Offline => {
+                "Your computer is not connected to a network. Reconnect and try again.".to_string()
+            }
+            LoadErrorKind::TooManyRedirects => format!(
+                "<strong>{}</strong> redirected too many times. Clearing its cookies may help.",
+                host
+            ),
+            LoadErrorKind::BlockedByContentFilter => {
+                "This address is on the ad block filter list. Turn ad blocking off to load it."
+                    .to_string()
+            }
+            LoadErrorKind::UnsupportedScheme => {
+                "abrw does not know how to open this kind of address.".to_string()
+            }
+            LoadErrorKind::Other => "Something went wrong while loading the page.".to_string(),
+        }
+    }
+}
+
+/// Sorts a load error into the kinds that get their own error page. Returns
+/// `None` for errors that should not show one, like a cancelled load.
+fn classify_load_error(error: &gtk::glib::Error, network_available: bool) -> Option<LoadErrorKind> {
+    if let Some(kind) = error.kind::<NetworkError>() {
+        match kind {
+            NetworkError::Cancelled => return None,
+            NetworkError::UnknownProtocol => return Some(LoadErrorKind::UnsupportedScheme),
+            _ => {}
+        }
+    }
+
+    if let Some(kind) = error.kind::<PolicyError>() {
+        match kind {
+            // Downloads and navigations replaced by another one end up here
+            PolicyError::FrameLoadInterruptedByPolicyChange | PolicyError::CannotShowMimeType => {
+                return None
+            }
+            PolicyError::CannotShowUri => return Some(LoadErrorKind::UnsupportedScheme),
+            _ => {}
+        }
+    }
+
+    if !network_available {
+        return Some(LoadErrorKind::Offline);
+    }
+
+    if error.kind::<ResolverError>().is_some() {
+        return Some(LoadErrorKind::Dns);
+    }
+
+    if let Some(kind) = error.kind::<IOErrorEnum>() {
+        match kind {
+            IOErrorEnum::Cancelled => return None,
+            IOErrorEnum::ConnectionRefused => return Some(LoadErrorKind::ConnectionRefused),
+            IOErrorEnum::TimedOut => return Some(LoadErrorKind::Timeout),
+            IOErrorEnum::NetworkUnreachable | IOErrorEnum::HostUnreachable => {
+                return Some(LoadErrorKind::Offline)
+            }
+            _ => {}
+        }
+    }
+
+    // Some errors only reach us as WebKit's generic transport error with
+    // libsoup's or the content blocker's message
+    let message = error.message().to_lowercase();
+
+    let kind = if message.contains("name or service not known")
+        || message.contains("could not resolve")
+        || message.contains("cannot resolve")
+    {
+        LoadErrorKind::Dns
+    } else if message.contains("connection refused") {
+        LoadErrorKind::ConnectionRefused
+    } else if message.contains("timed out") || message.contains("timeout") {
+        LoadErrorKind::Timeout
+    } else if message.contains("redirect") {
+        LoadErrorKind::TooManyRedirects
+    } else if message.contains("content blocker") || message.contains("content filter") {
+        LoadErrorKind::BlockedByContentFilter
+    } else {
+        LoadErrorKind::Other
+    };
+
+    Some(kind)
+}
+
+/// Shows the error page for a failed load of `uri`. Returns whether one was
+/// shown, as the load-failed handler has to report that to WebKit.
+pub fn show_error_page(webview: &WebView, uri: &str, error: &gtk::glib::Error) -> bool {
+    let network_available = NetworkMonitor::default().is_network_available();
+
+    let kind = match classify_load_error(error, network_available) {
+        Some(kind) => kind,
+        None => return false,
+    };
+
+    println!("Failed to load {}: {}", uri, error);
+
+    // Alternate HTML keeps the failed uri as the page's address, so reloading
+    // and history go to the site rather than the error page
+    webview.load_alternate_html(&error_page_html(kind, uri, error), uri, Some(uri));
+
+    true
+}
+
+fn error_page_html(kind: LoadErrorKind, uri: &str, error: &gtk::glib::Error) -> String {
+    let host = Url::parse(uri)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_else(|| uri.to_string());
+
+    let search_button = if kind == LoadErrorKind::Dns {
+        format!(
+            r#"<a class="button" href="{}">Search for this instead</a>"#,
+            escape_html(&search_uri(&host))
+        )
+    } else {
+        String::new()
+    };
+
+    format!(
+        r##"
+    <!DOCTYPE html>
+    <html lang="en">
+    <head>
+        <meta charset="UTF-8">
+        <title>{title}</title>
+        <style>
+            body, html {{
+                height: 100%;
+                margin: 0;
+                display: flex;
+                justify-content: center;
+                align-items: center;
+                background-color: #202020;
+                color: #f1f1f1;
+                font-family: "Hack", sans-serif;
+            }}
+            .container {{
+                max-width: 600px;
+                padding: 20px;
+            }}
+            .darkened {{
+                color: #888;
+                overflow-wrap: anywhere;
+            }}
+            .button {{
+                display: inline-block;
+                margin: 10px 10px 0 0;
+                background: #2a2a2a;
+                color: #f1f1f1;
+                border-radius: 7px;
+                padding: 8px 14px;
+                text-decoration: none;
+            }}
+            .button:hover {{
+                background: #333;
+            }}
+        </style>
+    </head>
+    <body>
+        <div class="container">
+            <h1>{title}</h1>
+            <p>{description}</p>
+            <p class="darkened">{uri}</p>
+            <p class="darkened">{error}</p>
+            <a class="button" href="{uri}">Try again</a>
+            {search_button}
+        </div>
+    </body>
+    </html>
+    "##,
+        title = kind.title(),
+        description = kind.description(&escape_html(&host)),
+        uri = escape_html(uri),
+        error = escape_html(error.message()),
+        search_button = search_button,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gtk::glib::Error;
+
+    fn classify(error: Error) -> Option<LoadErrorKind> {
+        classify_load_error(&error, true)
+    }
+
+    #[test]
+    fn cancelled_loads_get_no_error_page() {
+        assert_eq!(classify(Error::new(NetworkError::Cancelled, "")), None);
+        assert_eq!(classify(Error::new(IOErrorEnum::Cancelled, "")), None);
+        assert_eq!(
+            classify(Error::new(
+                PolicyError::FrameLoadInterruptedByPolicyChange,
+                ""
+            )),
+            None
+        );
+        assert_eq!(
+            classify(Error::new(PolicyError::CannotShowMimeType, "")),
+            None
+        );
+    }
+
+    #[test]
+    fn classifies_by_error_domain() {
+        assert_eq!(
+            classify(Error::new(NetworkError::UnknownProtocol, "")),
+            Some(LoadErrorKind::UnsupportedScheme)
+        );
+        assert_eq!(
+            classify(Error::new(PolicyError::CannotShowUri, "")),
+            Some(LoadErrorKind::UnsupportedScheme)
+        );
+        assert_eq!(
+            classify(Error::new(ResolverError::NotFound, "")),
+            Some(LoadErrorKind::Dns)
+        );
+        assert_eq!(
+            classify(Error::new(IOErrorEnum::ConnectionRefused, "")),
+            Some(LoadErrorKind::ConnectionRefused)
+        );
+        assert_eq!(
+            classify(Error::new(IOErrorEnum::TimedOut, "")),
+            Some(LoadErrorKind::Timeout)
+        );
+        assert_eq!(
+            classify(Error::new(IOErrorEnum::HostUnreachable, "")),
+            Some(LoadErrorKind::Offline)
+        );
+    }
+
+    #[test]
+    fn classifies_transport_errors_by_message() {
+        let transport = |message| classify(Error::new(NetworkError::Transport, message));
+
+        assert_eq!(
+            transport("Error resolving \"example.invalid\": Name or service not known"),
+            Some(LoadErrorKind::Dns)
+        );
+        assert_eq!(
+            transport("Could not connect: Connection refused"),
+            Some(LoadErrorKind::ConnectionRefused)
+        );
+        assert_eq!(
+            transport("Socket I/O timed out"),
+            Some(LoadErrorKind::Timeout)
+        );
+        assert_eq!(
+            transport("Too many redirects"),
+            Some(LoadErrorKind::TooManyRedirects)
+        );
+        assert_eq!(
+            transport("The URL was blocked by a content blocker"),
+            Some(LoadErrorKind::BlockedByContentFilter)
+        );
+        assert_eq!(transport("Something else"), Some(LoadErrorKind::Other));
+    }
+
+    #[test]
+    fn offline_wins_over_other_network_errors() {
+        let error = Error::new(NetworkError::Transport, "Connection refused");
+
+        assert_eq!(
+            classify_load_error(&error, false),
+            Some(LoadErrorKind::Offline)
+        );
+        assert_eq!(
+            classify_load_error(&Error::new(NetworkError::Cancelled, ""), false),
+            None
+        );
+    }
+}
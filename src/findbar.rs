@@ -0,0 +1,430 @@
+use gtk::gdk::keys::constants;
+use gtk::gdk::ModifierType;
+use gtk::glib::Propagation;
+use gtk::prelude::*;
+use gtk::{Box, Button, Entry, Label, Notebook, ToggleButton, Widget};
+use std::cell::Cell;
+use std::rc::Rc;
+use webkit2gtk::{FindController, FindControllerExt, FindOptions, WebView, WebViewExt};
+
+use crate::styles::apply_css_style;
+use crate::tabs::{page_webview, tab_browser_window, tab_state, TabState};
+use crate::{create_button_with_icon, BrowserWindow};
+
+const MAX_FIND_MATCHES: u32 = 1000;
+
+thread_local! {
+    // Set while the bar is filled in from a tab's state, so that doesn't
+    // trigger a new search
+    static SYNCING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Find bar contents for one tab. Every tab remembers its own search.
+#[derive(Debug, Clone)]
+pub struct FindState {
+    pub visible: bool,
+    pub text: String,
+    pub case_sensitive: bool,
+    pub word_starts: bool,
+    pub highlight_all: bool,
+    pub current: u32,
+    pub total: u32,
+}
+
+impl Default for FindState {
+    fn default() -> Self {
+        FindState {
+            visible: false,
+            text: String::new(),
+            case_sensitive: false,
+            word_starts: false,
+            highlight_all: true,
+            current: 0,
+            total: 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FindBar {
+    pub container: Box,
+    entry: Entry,
+    count_label: Label,
+    case_toggle: ToggleButton,
+    word_toggle: ToggleButton,
+    highlight_toggle: ToggleButton,
+}
+
+/// Find bar shown under the top bar. It searches the current tab of `notebook`.
+pub fn create_find_bar(notebook: &Notebook) -> FindBar {
+    let container = Box::new(gtk::Orientation::Horizontal, 5);
+
+    let entry = Entry::new();
+    entry.set_placeholder_text(Some("Find in page"));
+    entry.set_width_chars(30);
+
+    let count_label = Label::new(None);
+    let previous_button = create_button_with_icon("chevron-left");
+    let next_button = create_button_with_icon("chevron-right");
+
+    let case_toggle = ToggleButton::with_label("Aa");
+    case_toggle.set_tooltip_text(Some("Match case"));
+
+    // WebKit can only anchor matches at the start of words
+    let word_toggle = ToggleButton::with_label("Word starts");
+    word_toggle.set_tooltip_text(Some("Only match at the start of words"));
+
+    let highlight_toggle = ToggleButton::with_label("Highlight all");
+    highlight_toggle.set_active(true);
+
+    let close_button = Button::with_label("x");
+
+    container.pack_start(&entry, false, false, 5);
+    container.pack_start(&count_label, false, false, 5);
+    container.pack_start(&previous_button, false, false, 0);
+    container.pack_start(&next_button, false, false, 0);
+    container.pack_start(&case_toggle, false, false, 0);
+    container.pack_start(&word_toggle, false, false, 0);
+    container.pack_start(&highlight_toggle, false, false, 0);
+    container.pack_end(&close_button, false, false, 5);
+
+    apply_css_style(
+        &[
+            container.upcast_ref(),
+            entry.upcast_ref(),
+            count_label.upcast_ref(),
+            previous_button.upcast_ref(),
+            next_button.upcast_ref(),
+            case_toggle.upcast_ref(),
+            word_toggle.upcast_ref(),
+            highlight_toggle.upcast_ref(),
+            close_button.upcast_ref(),
+        ],
+        "
+        box { background: #202020; }
+        entry { background: #2a2a2a; border-color: #2d2d2d; }
+        label { color: #888; }
+        button { background: transparent; border: none; box-shadow: none; color: #888; }
+        button:hover { background: #2a2a2a; }
+        button:checked { background: #2a2a2a; color: #f1f1f1; }
+        ",
+    );
+
+    let find_bar = FindBar {
+        container: container.clone(),
+        entry: entry.clone(),
+        count_label,
+        case_toggle: case_toggle.clone(),
+        word_toggle: word_toggle.clone(),
+        highlight_toggle: highlight_toggle.clone(),
+    };
+
+    entry.connect_changed({
+        let notebook = notebook.clone();
+        move |entry| {
+            if SYNCING.with(|syncing| syncing.get()) {
+                return;
+            }
+
+            if let Some((_, state)) = current_tab(&notebook) {
+                let mut find = state.find.borrow_mut();
+                find.text = entry.text().to_string();
+                find.current = 1;
+            }
+
+            run_search(&notebook);
+        }
+    });
+
+    entry.connect_activate({
+        let notebook = notebook.clone();
+        move |_| find_next(&notebook)
+    });
+
+    entry.connect_key_press_event({
+        let notebook = notebook.clone();
+        move |_, key| match key.keyval() {
+            constants::Escape => {
+                hide_find_bar(&notebook);
+                Propagation::Stop
+            }
+            constants::Return | constants::KP_Enter
+                if key.state().contains(ModifierType::SHIFT_MASK) =>
+            {
+                find_previous(&notebook);
+                Propagation::Stop
+            }
+            _ => Propagation::Proceed,
+        }
+    });
+
+    for toggle in [&case_toggle, &word_toggle, &highlight_toggle] {
+        toggle.connect_toggled({
+            let notebook = notebook.clone();
+            let find_bar = find_bar.clone();
+            move |_| {
+                if SYNCING.with(|syncing| syncing.get()) {
+                    return;
+                }
+
+                if let Some((_, state)) = current_tab(&notebook) {
+                    let mut find = state.find.borrow_mut();
+                    find.case_sensitive = find_bar.case_toggle.is_active();
+                    find.word_starts = find_bar.word_toggle.is_active();
+                    find.highlight_all = find_bar.highlight_toggle.is_active();
+                    find.current = 1;
+                }
+
+                run_search(&notebook);
+            }
+        });
+    }
+
+    previous_button.connect_clicked({
+        let notebook = notebook.clone();
+        move |_| find_previous(&notebook)
+    });
+
+    next_button.connect_clicked({
+        let notebook = notebook.clone();
+        move |_| find_next(&notebook)
+    });
+
+    close_button.connect_clicked({
+        let notebook = notebook.clone();
+        move |_| hide_find_bar(&notebook)
+    });
+
+    container.show_all();
+
+    // Shown per tab by sync_find_bar, never by show_all
+    container.set_no_show_all(true);
+    container.hide();
+
+    find_bar
+}
+
+fn current_tab(notebook: &Notebook) -> Option<(Widget, Rc<TabState>)> {
+    let page = notebook.nth_page(notebook.current_page())?;
+    let state = tab_state(&page)?;
+
+    Some((page, state))
+}
+
+fn find_controller(page: &Widget) -> Option<FindController> {
+    page_webview(page).and_then(|webview| webview.find_controller())
+}
+
+fn find_options(find: &FindState) -> u32 {
+    let mut options = FindOptions::WRAP_AROUND;
+
+    if !find.case_sensitive {
+        options |= FindOptions::CASE_INSENSITIVE;
+    }
+
+    // WebKit can only anchor matches at the start of a word, not require
+    // them to end with it too
+    if find.word_starts {
+        options |= FindOptions::AT_WORD_STARTS;
+    }
+
+    options.bits()
+}
+
+pub fn show_find_bar(browser_window: &BrowserWindow) {
+    let notebook = &browser_window.notebook;
+
+    let (page, state) = match current_tab(notebook) {
+        Some(tab) => tab,
+        None => return,
+    };
+
+    state.find.borrow_mut().visible = true;
+    sync_find_bar(&browser_window.find_bar, &page);
+
+    browser_window.find_bar.entry.grab_focus();
+
+    if !state.find.borrow().text.is_empty() {
+        run_search(notebook);
+    }
+}
+
+pub fn hide_find_bar(notebook: &Notebook) {
+    let (page, state) = match current_tab(notebook) {
+        Some(tab) => tab,
+        None => return,
+    };
+
+    state.find.borrow_mut().visible = false;
+
+    if let Some(find_controller) = find_controller(&page) {
+        find_controller.search_finish();
+    }
+
+    if let Some(browser_window) = tab_browser_window(&page) {
+        sync_find_bar(&browser_window.find_bar, &page);
+    }
+
+    if let Some(webview) = page_webview(&page) {
+        webview.grab_focus();
+    }
+}
+
+fn run_search(notebook: &Notebook) {
+    let (page, state) = match current_tab(notebook) {
+        Some(tab) => tab,
+        None => return,
+    };
+
+    let find_controller = match find_controller(&page) {
+        Some(find_controller) => find_controller,
+        None => return,
+    };
+
+    let find = state.find.borrow().clone();
+
+    if find.text.is_empty() {
+        find_controller.search_finish();
+        state.find.borrow_mut().total = 0;
+        update_count_label(&page);
+        return;
+    }
+
+    let options = find_options(&find);
+
+    // Without highlight all, only the selected match is marked
+    find_controller.search(
+        &find.text,
+        options,
+        if find.highlight_all {
+            MAX_FIND_MATCHES
+        } else {
+            1
+        },
+    );
+    find_controller.count_matches(&find.text, options, MAX_FIND_MATCHES);
+}
+
+fn step_match(notebook: &Notebook, forward: bool) {
+    let (page, state) = match current_tab(notebook) {
+        Some(tab) => tab,
+        None => return,
+    };
+
+    let find_controller = match find_controller(&page) {
+        Some(find_controller) => find_controller,
+        None => return,
+    };
+
+    {
+        let mut find = state.find.borrow_mut();
+
+        if find.total == 0 {
+            return;
+        }
+
+        find.current = if forward {
+            find.current % find.total + 1
+        } else if find.current <= 1 {
+            find.total
+        } else {
+            find.current - 1
+        };
+    }
+
+    if forward {
+        find_controller.search_next();
+    } else {
+        find_controller.search_previous();
+    }
+
+    update_count_label(&page);
+}
+
+pub fn find_next(notebook: &Notebook) {
+    step_match(notebook, true);
+}
+
+pub fn find_previous(notebook: &Notebook) {
+    step_match(notebook, false);
+}
+
+/// Fills the bar in from the search state of `page`, the tab being shown.
+pub fn sync_find_bar(find_bar: &FindBar, page: &Widget) {
+    let state = match tab_state(page) {
+        Some(state) => state,
+        None => return,
+    };
+
+    let find = state.find.borrow().clone();
+
+    SYNCING.with(|syncing| syncing.set(true));
+
+    find_bar.entry.set_text(&find.text);
+    find_bar.case_toggle.set_active(find.case_sensitive);
+    find_bar.word_toggle.set_active(find.word_starts);
+    find_bar.highlight_toggle.set_active(find.highlight_all);
+    find_bar.count_label.set_text(&count_text(&find));
+    find_bar.container.set_visible(find.visible);
+
+    SYNCING.with(|syncing| syncing.set(false));
+}
+
+fn count_text(find: &FindState) -> String {
+    if find.text.is_empty() {
+        String::new()
+    } else if find.total == 0 {
+        "No matches".to_string()
+    } else {
+        format!("{} of {}", find.current, find.total)
+    }
+}
+
+fn update_count_label(page: &Widget) {
+    let browser_window = match tab_browser_window(page) {
+        Some(browser_window) => browser_window,
+        None => return,
+    };
+
+    let notebook = &browser_window.notebook;
+
+    if notebook.nth_page(notebook.current_page()).as_ref() != Some(page) {
+        return;
+    }
+
+    if let Some(state) = tab_state(page) {
+        browser_window
+            .find_bar
+            .count_label
+            .set_text(&count_text(&state.find.borrow()));
+    }
+}
+
+/// Keeps a tab's match count up to date with its webview's find controller.
+pub fn connect_find_controller(webview: &WebView, state: Rc<TabState>) {
+    let find_controller = match webview.find_controller() {
+        Some(find_controller) => find_controller,
+        None => return,
+    };
+
+    let webview = webview.downgrade();
+
+    let set_total = move |total: u32| {
+        {
+            let mut find = state.find.borrow_mut();
+            find.total = total;
+            find.current = find.current.clamp(1, total.max(1));
+        }
+
+        if let Some(page) = webview.upgrade().and_then(|webview| webview.parent()) {
+            update_count_label(&page);
+        }
+    };
+
+    find_controller.connect_counted_matches({
+        let set_total = set_total.clone();
+        move |_, count| set_total(count)
+    });
+
+    find_controller.connect_failed_to_find_text(move |_| set_total(0));
+}
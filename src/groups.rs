@@ -0,0 +1,356 @@
+use gtk::glib::Propagation;
+use gtk::prelude::*;
+use gtk::{Box, Button, ColorButton, Dialog, Entry, Label, Menu, MenuItem, Notebook, Widget};
+use serde::{Deserialize, Serialize};
+
+use crate::create_empty_window;
+use crate::session::save_session;
+use crate::sidebar::refresh_tab_sidebar;
+use crate::styles::apply_css_style;
+use crate::tabs::{close_window_if_empty, is_tab_pinned, page_notebook, set_tab_pinned, tab_state};
+
+const GROUP_COLORS: [&str; 6] = [
+    "#5e81ac", "#a3be8c", "#ebcb8b", "#bf616a", "#b48ead", "#88c0d0",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TabGroup {
+    pub name: String,
+    pub color: String,
+}
+
+pub fn tab_group(page: &impl IsA<Widget>) -> Option<TabGroup> {
+    tab_state(page).and_then(|state| state.group.borrow().clone())
+}
+
+pub fn set_tab_group(page: &impl IsA<Widget>, group: Option<TabGroup>) {
+    let state = match tab_state(page) {
+        Some(state) => state,
+        None => return,
+    };
+
+    // The color goes into CSS as is, so it has to be one and nothing more
+    if let Some(group) = &group {
+        if gtk::gdk::RGBA::parse(&group.color).is_err() {
+            println!(
+                "Invalid color for tab group {}: {}",
+                group.name, group.color
+            );
+            return;
+        }
+    }
+
+    let css = match &group {
+        Some(group) => format!("box {{ border-bottom: 2px solid {}; }}", group.color),
+        None => String::new(),
+    };
+
+    state
+        .group_style
+        .load_from_data(css.as_bytes())
+        .expect("Failed to load css");
+
+    state.group.replace(group);
+
+    if let Some(notebook) = page_notebook(page) {
+        refresh_group_bar(&notebook);
+        refresh_tab_sidebar(&notebook);
+    }
+
+    save_session();
+}
+
+/// Groups used by the tabs of `notebook`, in the order they first appear.
+pub fn notebook_groups(notebook: &Notebook) -> Vec<TabGroup> {
+    let mut groups: Vec<TabGroup> = Vec::new();
+
+    for page in notebook.children() {
+        if let Some(group) = tab_group(&page) {
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+    }
+
+    groups
+}
+
+fn group_pages(notebook: &Notebook, group: &TabGroup) -> Vec<Widget> {
+    notebook
+        .children()
+        .into_iter()
+        .filter(|page| tab_group(page).as_ref() == Some(group))
+        .collect()
+}
+
+/// A group is collapsed when none of its tabs are shown.
+pub fn is_group_collapsed(notebook: &Notebook, group: &TabGroup) -> bool {
+    group_pages(notebook, group)
+        .iter()
+        .all(|page| !page.is_visible())
+}
+
+pub fn set_group_collapsed(notebook: &Notebook, group: &TabGroup, collapsed: bool) {
+    let pages = group_pages(notebook, group);
+
+    if collapsed {
+        let current_page = notebook.nth_page(notebook.current_page());
+
+        if current_page
+            .as_ref()
+            .is_some_and(|page| pages.contains(page))
+        {
+            // Move away from the group first, there has to be a tab left to show
+            match notebook
+                .children()
+                .into_iter()
+                .find(|page| page.is_visible() && !pages.contains(page))
+            {
+                Some(page) => notebook.set_current_page(notebook.page_num(&page)),
+                None => return,
+            }
+        }
+    }
+
+    for page in pages {
+        page.set_visible(!collapsed);
+    }
+
+    refresh_group_bar(notebook);
+    refresh_tab_sidebar(notebook);
+    save_session();
+}
+
+/// Switches to `page`, expanding its group first when it is collapsed, as
+/// the notebook doesn't switch to hidden pages.
+pub fn select_tab(notebook: &Notebook, page: &Widget) {
+    if !page.is_visible() {
+        if let Some(group) = tab_group(page) {
+            set_group_collapsed(notebook, &group, false);
+        }
+    }
+
+    notebook.set_current_page(notebook.page_num(page));
+}
+
+/// Rebuilds the group chips shown at the start of the notebook header.
+pub fn refresh_group_bar(notebook: &Notebook) {
+    let group_bar = match notebook
+        .action_widget(gtk::PackType::Start)
+        .and_then(|widget| widget.downcast::<Box>().ok())
+    {
+        Some(group_bar) => group_bar,
+        None => return,
+    };
+
+    for child in group_bar.children() {
+        group_bar.remove(&child);
+    }
+
+    for group in notebook_groups(notebook) {
+        let collapsed = is_group_collapsed(notebook, &group);
+        let chip = Button::with_label(&format!(
+            "{} {}",
+            if collapsed { "▸" } else { "▾" },
+            group.name
+        ));
+
+        apply_css_style(
+            &[chip.upcast_ref()],
+            &format!(
+                "
+                button {{ background: {}; color: #202020; border: none; box-shadow: none; border-radius: 7px; margin: 4px; padding: 2px 8px; }}
+                ",
+                group.color
+            ),
+        );
+
+        chip.connect_clicked({
+            let notebook = notebook.clone();
+            let group = group.clone();
+            move |_| set_group_collapsed(&notebook, &group, !collapsed)
+        });
+
+        chip.connect_button_press_event({
+            let notebook = notebook.clone();
+            let group = group.clone();
+            move |_, event| {
+                if event.button() != 3 {
+                    return Propagation::Proceed;
+                }
+
+                show_group_menu(&notebook, &group, event);
+                Propagation::Stop
+            }
+        });
+
+        group_bar.pack_start(&chip, false, false, 0);
+    }
+
+    group_bar.show_all();
+}
+
+pub fn show_group_menu(notebook: &Notebook, group: &TabGroup, event: &gtk::gdk::EventButton) {
+    let menu = Menu::new();
+
+    let move_item = MenuItem::with_label("Move group to new window");
+    move_item.connect_activate({
+        let notebook = notebook.clone();
+        let group = group.clone();
+        move |_| move_group_to_new_window(&notebook, &group)
+    });
+
+    let ungroup_item = MenuItem::with_label("Ungroup tabs");
+    ungroup_item.connect_activate({
+        let notebook = notebook.clone();
+        let group = group.clone();
+        move |_| {
+            for page in group_pages(&notebook, &group) {
+                page.show();
+                set_tab_group(&page, None);
+            }
+        }
+    });
+
+    menu.append(&move_item);
+    menu.append(&ungroup_item);
+    menu.show_all();
+    menu.popup_at_pointer(Some(event));
+}
+
+pub fn move_group_to_new_window(notebook: &Notebook, group: &TabGroup) {
+    let pages = group_pages(notebook, group);
+
+    if pages.is_empty() {
+        return;
+    }
+
+    let browser_window = create_empty_window();
+
+    for page in pages {
+        let tab_label = match notebook.tab_label(&page) {
+            Some(tab_label) => tab_label,
+            None => continue,
+        };
+
+        let pinned = is_tab_pinned(&page);
+
+        notebook.remove(&page);
+        page.show();
+        browser_window.notebook.append_page(&page, Some(&tab_label));
+
+        if pinned {
+            set_tab_pinned(&page, true);
+        }
+    }
+
+    refresh_group_bar(notebook);
+    refresh_group_bar(&browser_window.notebook);
+
+    close_window_if_empty(notebook);
+}
+
+/// Adds the grouping entries to a tab's context menu.
+pub fn append_group_menu_items(menu: &Menu, page: &impl IsA<Widget>) {
+    let notebook = match page_notebook(page) {
+        Some(notebook) => notebook,
+        None => return,
+    };
+
+    let group_menu = Menu::new();
+    let current_group = tab_group(page);
+
+    for group in notebook_groups(&notebook) {
+        if current_group.as_ref() == Some(&group) {
+            continue;
+        }
+
+        let item = MenuItem::with_label(&group.name);
+        item.connect_activate({
+            let page = page.clone().upcast::<Widget>();
+            move |_| set_tab_group(&page, Some(group.clone()))
+        });
+
+        group_menu.append(&item);
+    }
+
+    let new_group_item = MenuItem::with_label("New group...");
+    new_group_item.connect_activate({
+        let page = page.clone().upcast::<Widget>();
+        let notebook = notebook.clone();
+        move |_| show_new_group_dialog(&notebook, &page)
+    });
+
+    group_menu.append(&new_group_item);
+
+    let add_item = MenuItem::with_label("Add to group");
+    add_item.set_submenu(Some(&group_menu));
+    menu.append(&add_item);
+
+    if current_group.is_some() {
+        let remove_item = MenuItem::with_label("Remove from group");
+        remove_item.connect_activate({
+            let page = page.clone().upcast::<Widget>();
+            move |_| set_tab_group(&page, None)
+        });
+
+        menu.append(&remove_item);
+    }
+}
+
+fn show_new_group_dialog(notebook: &Notebook, page: &Widget) {
+    let dialog = Dialog::with_buttons(
+        Some("New tab group"),
+        notebook
+            .toplevel()
+            .and_then(|toplevel| toplevel.downcast::<gtk::Window>().ok())
+            .as_ref(),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel),
+            ("Create", gtk::ResponseType::Accept),
+        ],
+    );
+
+    let name_entry = Entry::new();
+    name_entry.set_placeholder_text(Some("Group name"));
+    name_entry.set_activates_default(true);
+
+    let next_color = GROUP_COLORS[notebook_groups(notebook).len() % GROUP_COLORS.len()];
+    let color_button = ColorButton::with_rgba(
+        &next_color
+            .parse::<gtk::gdk::RGBA>()
+            .expect("Failed to parse group color"),
+    );
+
+    let row = Box::new(gtk::Orientation::Horizontal, 5);
+    row.pack_start(&Label::new(Some("Name")), false, false, 5);
+    row.pack_start(&name_entry, true, true, 5);
+    row.pack_end(&color_button, false, false, 5);
+
+    dialog.content_area().pack_start(&row, false, false, 5);
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    dialog.connect_response({
+        let page = page.clone();
+        move |dialog, response| {
+            let name = name_entry.text().trim().to_string();
+
+            if response == gtk::ResponseType::Accept && !name.is_empty() {
+                let rgba = color_button.rgba();
+                let color = format!(
+                    "#{:02x}{:02x}{:02x}",
+                    (rgba.red() * 255.0) as u8,
+                    (rgba.green() * 255.0) as u8,
+                    (rgba.blue() * 255.0) as u8
+                );
+
+                set_tab_group(&page, Some(TabGroup { name, color }));
+            }
+
+            dialog.close();
+        }
+    });
+
+    dialog.show_all();
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::settings::Settings;
+use crate::storage::{load_json, save_json};
+
+/// Oldest entries are dropped beyond this many
+const MAX_HISTORY_ENTRIES: usize = 5000;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HistoryEntry {
+    pub uri: String,
+    pub title: String,
+    pub visit_count: u32,
+    /// Seconds since the Unix epoch
+    pub last_visit: u64,
+}
+
+/// Pages visited across all tabs and windows, most recent last.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    pub fn save(&self) {
+        save_json("history.json", self);
+    }
+
+    pub fn load() -> History {
+        load_json("history.json")
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Records a visit to `uri`. Nothing is kept while browsing privately, and
+/// only web pages are worth remembering.
+pub fn record_visit(uri: &str, title: &str) {
+    if Settings::load().private_browsing
+        || !(uri.starts_with("http://") || uri.starts_with("https://"))
+    {
+        return;
+    }
+
+    let mut history = History::load();
+
+    let mut entry = match history.entries.iter().position(|entry| entry.uri == uri) {
+        Some(index) => history.entries.remove(index),
+        None => HistoryEntry {
+            uri: uri.to_string(),
+            ..HistoryEntry::default()
+        },
+    };
+
+    if !title.is_empty() {
+        entry.title = title.to_string();
+    }
+    entry.visit_count += 1;
+    entry.last_visit = now();
+
+    history.entries.push(entry);
+
+    if history.entries.len() > MAX_HISTORY_ENTRIES {
+        let excess = history.entries.len() - MAX_HISTORY_ENTRIES;
+        history.entries.drain(..excess);
+    }
+
+    history.save();
+}
+
+pub fn clear_history() {
+    History::default().save();
+}
@@ -0,0 +1,490 @@
+use gtk::prelude::*;
+use gtk::Box;
+use javascriptcore::ValueExt;
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::rc::Rc;
+use url::{Host, Url};
+use webkit2gtk::{
+    LoadEvent, NavigationPolicyDecision, NavigationPolicyDecisionExt, NavigationType,
+    PolicyDecision, PolicyDecisionExt, PolicyDecisionType, URIRequestExt, UserContentManagerExt,
+    WebView, WebViewExt,
+};
+
+use crate::reader::escape_html;
+use crate::settings::{create_settings_list, Settings, SettingsListRow};
+use crate::storage::{load_json, save_json};
+use crate::tabs::TabState;
+
+/// Script message the upgrade error page uses to fall back to HTTP
+pub const HTTPS_MESSAGE: &str = "abrwHttps";
+
+/// Hosts the user allowed to load over plain HTTP in HTTPS-only mode.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HttpsExceptions {
+    pub hosts: Vec<String>,
+}
+
+impl HttpsExceptions {
+    pub fn save(&self) {
+        save_json("https_exceptions.json", self);
+    }
+
+    pub fn load() -> HttpsExceptions {
+        load_json("https_exceptions.json")
+    }
+}
+
+/// An HTTP navigation the tab upgraded to HTTPS.
+pub struct HttpsUpgrade {
+    pub http_uri: String,
+    pub https_uri: String,
+    /// The HTTPS load failed and the tab shows the fallback page
+    pub failed: bool,
+    /// Random value only the fallback page knows, which it sends to continue
+    pub token: String,
+}
+
+fn is_private_ipv4(ip: &Ipv4Addr) -> bool {
+    ip.is_private() || ip.is_loopback() || ip.is_link_local()
+}
+
+fn is_private_ipv6(ip: &Ipv6Addr) -> bool {
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_private_ipv4(&ipv4);
+    }
+
+    let first = ip.segments()[0];
+
+    // Unique local (fc00::/7) and link-local (fe80::/10) addresses
+    ip.is_loopback() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+}
+
+/// Names only used inside local networks
+const LOCAL_DOMAIN_SUFFIXES: [&str; 5] =
+    [".localhost", ".local", ".lan", ".internal", ".home.arpa"];
+
+/// Local machines and private networks rarely have certificates, so they are
+/// never upgraded.
+fn is_exempt_host(host: &Host<&str>) -> bool {
+    match host {
+        Host::Domain(domain) => {
+            let domain = domain.trim_end_matches('.');
+
+            // Single label names like "nas" or "router" are intranet hosts
+            !domain.contains('.')
+                || LOCAL_DOMAIN_SUFFIXES
+                    .iter()
+                    .any(|suffix| domain.ends_with(suffix))
+        }
+        Host::Ipv4(ip) => is_private_ipv4(ip),
+        Host::Ipv6(ip) => is_private_ipv6(ip),
+    }
+}
+
+/// The HTTPS address to load instead of `uri`, if HTTPS-only mode applies to it.
+fn upgraded_uri(uri: &str, exceptions: &HttpsExceptions) -> Option<String> {
+    let mut url = Url::parse(uri).ok()?;
+
+    // An explicit port belongs to the HTTP server, the default one is never
+    // kept by the parser
+    if url.scheme() != "http" || url.port().is_some() {
+        return None;
+    }
+
+    let host = url.host()?;
+
+    if is_exempt_host(&host)
+        || exceptions
+            .hosts
+            .iter()
+            .any(|exception| *exception == host.to_string())
+    {
+        return None;
+    }
+
+    url.set_scheme("https").ok()?;
+    Some(url.to_string())
+}
+
+pub fn add_https_message_handler(webview: &WebView) {
+    if let Some(content_manager) = webview.user_content_manager() {
+        content_manager.register_script_message_handler(HTTPS_MESSAGE);
+    }
+}
+
+/// Remembers that `http_uri` is being loaded as `https_uri` instead.
+fn start_upgrade(state: &TabState, http_uri: &str, https_uri: &str) {
+    state.https_upgrade.replace(Some(HttpsUpgrade {
+        http_uri: http_uri.to_string(),
+        https_uri: https_uri.to_string(),
+        failed: false,
+        token: gtk::glib::uuid_string_random().to_string(),
+    }));
+}
+
+/// Upgrades HTTP navigations in `webview` to HTTPS while HTTPS-only mode is
+/// on, before the HTTP request is sent.
+fn handle_navigation_policy(
+    webview: &WebView,
+    state: &TabState,
+    decision: &PolicyDecision,
+) -> bool {
+    let decision = match decision.downcast_ref::<NavigationPolicyDecision>() {
+        Some(decision) => decision,
+        None => return false,
+    };
+
+    let (mut action, request) = match decision
+        .navigation_action()
+        .and_then(|action| action.request().map(|request| (action, request)))
+    {
+        Some(navigation) => navigation,
+        None => return false,
+    };
+
+    let http_uri = match request.uri() {
+        Some(uri) => uri.to_string(),
+        None => return false,
+    };
+
+    if !Settings::load().https_only {
+        return false;
+    }
+
+    let https_uri = match upgraded_uri(&http_uri, &HttpsExceptions::load()) {
+        Some(https_uri) => https_uri,
+        None => return false,
+    };
+
+    // WebKit doesn't say which frame a navigation is for. Frames mostly load
+    // while their page does and without a user gesture, so those are left
+    // to the load-changed handler, which only sees the main frame.
+    let is_redirect = action.is_redirect();
+
+    if action.navigation_type() == NavigationType::Other
+        && !is_redirect
+        && !action.is_user_gesture()
+        && webview.is_loading()
+    {
+        return false;
+    }
+
+    decision.ignore();
+
+    let redirect_loop = is_redirect
+        && state
+            .https_upgrade
+            .borrow()
+            .as_ref()
+            .is_some_and(|upgrade| upgrade.https_uri == https_uri);
+
+    // The HTTPS site sent us back to HTTP, upgrading again would never end
+    if redirect_loop {
+        show_https_fallback(
+            webview,
+            state,
+            &https_uri,
+            "The site redirects its HTTPS address to HTTP.",
+        );
+        return true;
+    }
+
+    start_upgrade(state, &http_uri, &https_uri);
+
+    // Loading a URI again can't send a form, so forms wait for the user
+    if request.http_method().as_deref() == Some("POST") {
+        show_https_fallback(
+            webview,
+            state,
+            &https_uri,
+            "The form would be sent without encryption.",
+        );
+        return true;
+    }
+
+    webview.load_uri(&https_uri);
+    true
+}
+
+/// Upgrades HTTP navigations in `webview` to HTTPS while HTTPS-only mode is
+/// on, and falls back to HTTP when the user asks for it on the error page.
+pub fn connect_https_upgrades(webview: &WebView, state: Rc<TabState>) {
+    webview.connect_decide_policy({
+        let state = state.clone();
+        move |webview, decision, decision_type| {
+            decision_type == PolicyDecisionType::NavigationAction
+                && handle_navigation_policy(webview, &state, decision)
+        }
+    });
+
+    webview.connect_load_changed({
+        let state = state.clone();
+        move |webview, event| {
+            let uri = match webview.uri() {
+                Some(uri) => uri.to_string(),
+                None => return,
+            };
+
+            if event == LoadEvent::Committed {
+                let other_page = state
+                    .https_upgrade
+                    .borrow()
+                    .as_ref()
+                    .is_some_and(|upgrade| upgrade.https_uri != uri);
+
+                if other_page {
+                    state.https_upgrade.replace(None);
+                }
+            }
+
+            // Main frame loads the policy check had to let through
+            if event != LoadEvent::Started || !Settings::load().https_only {
+                return;
+            }
+
+            if let Some(https_uri) = upgraded_uri(&uri, &HttpsExceptions::load()) {
+                start_upgrade(&state, &uri, &https_uri);
+
+                // Starting another load cancels this one
+                webview.load_uri(&https_uri);
+            }
+        }
+    });
+
+    let content_manager = match webview.user_content_manager() {
+        Some(content_manager) => content_manager,
+        None => return,
+    };
+
+    let webview = webview.downgrade();
+
+    // Any page could post to the handler, and webview.uri() already reports
+    // the error page while the previous page still runs, so the error page
+    // proves itself with its token
+    content_manager.connect_script_message_received(Some(HTTPS_MESSAGE), move |_, result| {
+        let webview = match webview.upgrade() {
+            Some(webview) => webview,
+            None => return,
+        };
+
+        let token = match result.js_value() {
+            Some(value) => value.to_str().to_string(),
+            None => return,
+        };
+
+        let http_uri = match state.https_upgrade.borrow().as_ref() {
+            Some(upgrade)
+                if upgrade.failed
+                    && upgrade.token == token
+                    && webview.uri().as_deref() == Some(upgrade.https_uri.as_str()) =>
+            {
+                upgrade.http_uri.clone()
+            }
+            _ => return,
+        };
+
+        if let Some(host) = Url::parse(&http_uri)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+        {
+            let mut exceptions = HttpsExceptions::load();
+
+            if !exceptions.hosts.contains(&host) {
+                exceptions.hosts.push(host);
+                exceptions.save();
+            }
+        }
+
+        state.https_upgrade.replace(None);
+        webview.load_uri(&http_uri);
+    });
+}
+
+/// Shows the fallback page if `uri` failed to load after being upgraded to
+/// HTTPS. Returns whether it did.
+pub fn show_https_fallback(webview: &WebView, state: &TabState, uri: &str, reason: &str) -> bool {
+    let (http_uri, token) = {
+        let mut upgrade = state.https_upgrade.borrow_mut();
+
+        match upgrade.as_mut() {
+            Some(upgrade) if upgrade.https_uri == uri => {
+                upgrade.failed = true;
+                (upgrade.http_uri.clone(), upgrade.token.clone())
+            }
+            _ => return false,
+        }
+    };
+
+    let host = Url::parse(&http_uri)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_default();
+
+    let html = format!(
+        r##"
+    <!DOCTYPE html>
+    <html lang="en">
+    <head>
+        <meta charset="UTF-8">
+        <title>Secure connection unavailable</title>
+        <style>
+            body, html {{
+                height: 100%;
+                margin: 0;
+                display: flex;
+                justify-content: center;
+                align-items: center;
+                background-color: #202020;
+                color: #f1f1f1;
+                font-family: "Hack", sans-serif;
+            }}
+            .container {{
+                max-width: 600px;
+                padding: 20px;
+            }}
+            .darkened {{
+                color: #888;
+                overflow-wrap: anywhere;
+            }}
+            button {{
+                margin: 10px 10px 0 0;
+                background: #2a2a2a;
+                color: #f1f1f1;
+                border: none;
+                border-radius: 7px;
+                padding: 8px 14px;
+                cursor: pointer;
+            }}
+            button:hover {{
+                background: #333;
+            }}
+        </style>
+    </head>
+    <body>
+        <div class="container">
+            <h1>Secure connection unavailable</h1>
+            <p>HTTPS-only mode is on, but <strong>{host}</strong> could not be loaded over HTTPS.</p>
+            <p>If you continue, information you send to this site can be read and changed by others.</p>
+            <p class="darkened">{reason}</p>
+            <button onclick="history.back()">Go back</button>
+            <button onclick="window.webkit.messageHandlers.abrwHttps.postMessage('{token}')">Continue to HTTP site</button>
+        </div>
+    </body>
+    </html>
+    "##,
+        host = escape_html(&host),
+        reason = escape_html(reason),
+        token = token,
+    );
+
+    webview.load_alternate_html(&html, uri, Some(uri));
+
+    true
+}
+
+/// Lists the hosts exempt from HTTPS-only mode in the settings window.
+pub fn create_https_exceptions_section(parent_box: &Box) {
+    let rows = HttpsExceptions::load()
+        .hosts
+        .into_iter()
+        .map(|host| SettingsListRow {
+            label: host.clone(),
+            detail: None,
+            key: host,
+        })
+        .collect();
+
+    create_settings_list(
+        parent_box,
+        "HTTPS-only exceptions",
+        "No exceptions",
+        ("Remove", None),
+        rows,
+        |host| {
+            let mut exceptions = HttpsExceptions::load();
+            exceptions.hosts.retain(|exception| exception != host);
+            exceptions.save();
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exempt(uri: &str) -> bool {
+        let url = Url::parse(uri).unwrap();
+        is_exempt_host(&url.host().unwrap())
+    }
+
+    #[test]
+    fn local_hosts_are_exempt() {
+        for uri in [
+            "http://localhost/",
+            "http://app.localhost/",
+            "http://printer.local/",
+            "http://nas/",
+            "http://nas./",
+            "http://router.lan/",
+            "http://grafana.internal/",
+            "http://pi.home.arpa/",
+            "http://127.0.0.1/",
+            "http://10.1.2.3/",
+            "http://172.16.0.1/",
+            "http://192.168.1.1/",
+            "http://169.254.1.1/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://[::ffff:192.168.1.1]/",
+        ] {
+            assert!(exempt(uri), "{} should be exempt", uri);
+        }
+    }
+
+    #[test]
+    fn public_hosts_are_not_exempt() {
+        for uri in [
+            "http://example.com/",
+            "http://8.8.8.8/",
+            "http://172.32.0.1/",
+            "http://[2001:db8::1]/",
+            "http://[::ffff:8.8.8.8]/",
+        ] {
+            assert!(!exempt(uri), "{} should not be exempt", uri);
+        }
+    }
+
+    #[test]
+    fn upgrades_http_addresses() {
+        let exceptions = HttpsExceptions::default();
+
+        assert_eq!(
+            upgraded_uri("http://example.com/path?q=1#top", &exceptions).as_deref(),
+            Some("https://example.com/path?q=1#top")
+        );
+        assert_eq!(
+            upgraded_uri("http://example.com:80/", &exceptions).as_deref(),
+            Some("https://example.com/")
+        );
+    }
+
+    #[test]
+    fn leaves_other_addresses_alone() {
+        let exceptions = HttpsExceptions {
+            hosts: vec!["old.example.com".to_string()],
+        };
+
+        for uri in [
+            "https://example.com/",
+            "http://example.com:8080/",
+            "http://old.example.com/",
+            "http://localhost/",
+            "abrw://newtab",
+            "file:///tmp/page.html",
+        ] {
+            assert_eq!(upgraded_uri(uri, &exceptions), None, "{}", uri);
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use gtk::gio::MemoryInputStream;
+use gtk::glib::Bytes;
+use gtk::prelude::*;
+use javascriptcore::ValueExt;
+use url::Url;
+use webkit2gtk::{
+    DownloadExt, SecurityManagerExt, URISchemeRequest, URISchemeRequestExt, UserContentManagerExt,
+    WebContext, WebContextExt, WebView, WebViewExt,
+};
+
+use crate::bookmarks::Bookmarks;
+use crate::connections::input_to_uri;
+use crate::downloads::{with_downloads, DownloadStatus};
+use crate::history::{clear_history, History};
+use crate::newtab::{newtab_page, pin_tile, serve_favicon, unpin_tile};
+use crate::reader::escape_html;
+use crate::settings::{apply_setting_changes, show_settings_window, Settings};
+use crate::source::serve_source_page;
+
+pub const INTERNAL_SCHEME: &str = "abrw";
+pub const NEWTAB_URI: &str = "abrw://newtab";
+
+/// Script message internal pages use to call back into the browser. Only
+/// messages carrying a token the browser gave out for an internal page are
+/// acted on.
+pub const INTERNAL_MESSAGE: &str = "abrwInternal";
+
+/// Replaced by the page token when an internal page is served
+const PAGE_TOKEN_PLACEHOLDER: &str = "ABRW_PAGE_TOKEN";
+const PAGE_TOKENS_KEY: &str = "abrw-page-tokens";
+// Older internal pages stay valid for going back to them
+const MAX_PAGE_TOKENS: usize = 20;
+
+const INTERNAL_PAGE_STYLE: &str = r#"
+    body, html {
+        margin: 0;
+        background-color: #202020;
+        color: #f1f1f1;
+        font-family: "Hack", sans-serif;
+    }
+    main {
+        max-width: 800px;
+        margin: 0 auto;
+        padding: 40px 20px;
+    }
+    nav a {
+        margin-right: 15px;
+    }
+    a {
+        color: #f1f1f1;
+    }
+    .darkened {
+        color: #888;
+    }
+    button {
+        background: #2a2a2a;
+        color: #f1f1f1;
+        border: none;
+        border-radius: 7px;
+        padding: 5px 10px;
+        cursor: pointer;
+    }
+    button:hover {
+        background: #333;
+    }
+    ul {
+        list-style: none;
+        padding: 0;
+    }
+    li {
+        display: flex;
+        justify-content: space-between;
+        align-items: center;
+        padding: 8px 0;
+        border-bottom: 1px solid #2a2a2a;
+        overflow-wrap: anywhere;
+    }
+"#;
+
+pub const INTERNAL_PAGE_SCRIPT: &str = r#"
+    function abrw(message) {
+        message.token = "ABRW_PAGE_TOKEN";
+        window.webkit.messageHandlers.abrwInternal.postMessage(JSON.stringify(message));
+    }
+"#;
+
+/// Serves abrw:// pages from `context`. The scheme is local, so web pages
+/// cannot link to or embed internal pages.
+pub fn register_internal_scheme(context: &WebContext) {
+    if let Some(security_manager) = context.security_manager() {
+        security_manager.register_uri_scheme_as_local(INTERNAL_SCHEME);
+        security_manager.register_uri_scheme_as_secure(INTERNAL_SCHEME);
+    }
+
+    context.register_uri_scheme(INTERNAL_SCHEME, handle_internal_request);
+}
+
+fn handle_internal_request(request: &URISchemeRequest) {
+    let page = request
+        .uri()
+        .and_then(|uri| Url::parse(&uri).ok())
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_default();
+
+    // Source views and favicons are served once they are at hand
+    if page == "source" {
+        serve_source_page(request);
+        return;
+    }
+
+    if page == "favicon" {
+        serve_favicon(request);
+        return;
+    }
+
+    let html = match page.as_str() {
+        "newtab" => newtab_page(),
+        "settings" => settings_page(),
+        "history" => history_page(),
+        "bookmarks" => bookmarks_page(),
+        "downloads" => downloads_page(),
+        "about" => about_page(),
+        _ => internal_page(
+            "Not found",
+            &format!(
+                "<p class=\"darkened\">There is no internal page called abrw://{}.</p>",
+                escape_html(&page)
+            ),
+        ),
+    };
+
+    // The script with the placeholder is in the head, before any page content
+    let html = match request.web_view() {
+        Some(webview) => html.replacen(PAGE_TOKEN_PLACEHOLDER, &new_page_token(&webview), 1),
+        None => html,
+    };
+
+    let bytes = Bytes::from_owned(html.into_bytes());
+    let stream = MemoryInputStream::from_bytes(&bytes);
+
+    request.finish(&stream, bytes.len() as i64, Some("text/html"));
+}
+
+fn page_tokens(webview: &WebView) -> Vec<String> {
+    unsafe {
+        webview
+            .data::<Vec<String>>(PAGE_TOKENS_KEY)
+            .map(|tokens| tokens.as_ref().clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Gives out a random token for an internal page about to be shown in
+/// `webview`.
+fn new_page_token(webview: &WebView) -> String {
+    let token = gtk::glib::uuid_string_random().to_string();
+    let mut tokens = page_tokens(webview);
+
+    tokens.push(token.clone());
+    if tokens.len() > MAX_PAGE_TOKENS {
+        tokens.remove(0);
+    }
+
+    unsafe { webview.set_data(PAGE_TOKENS_KEY, tokens) };
+
+    token
+}
+
+/// Wraps `body` in the layout shared by internal pages.
+fn internal_page(title: &str, body: &str) -> String {
+    format!(
+        r##"
+    <!DOCTYPE html>
+    <html lang="en">
+    <head>
+        <meta charset="UTF-8">
+        <meta name="viewport" content="width=device-width, initial-scale=1.0">
+        <title>{title}</title>
+        <style>{style}</style>
+        <script>{script}</script>
+    </head>
+    <body>
+        <main>
+            <nav class="darkened">
+                <a href="abrw://history">History</a>
+                <a href="abrw://bookmarks">Bookmarks</a>
+                <a href="abrw://downloads">Downloads</a>
+                <a href="abrw://settings">Settings</a>
+                <a href="abrw://about">About</a>
+            </nav>
+            <h1>{title}</h1>
+            {body}
+        </main>
+    </body>
+    </html>
+    "##,
+        title = escape_html(title),
+        style = INTERNAL_PAGE_STYLE,
+        script = INTERNAL_PAGE_SCRIPT,
+        body = body,
+    )
+}
+
+/// Turns a field name such as `enable_local_storage` into "Enable local storage".
+fn setting_label(key: &str) -> String {
+    let label = key.replace('_', " ");
+    let mut chars = label.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => label,
+    }
+}
+
+fn settings_page() -> String {
+    let settings = serde_json::to_value(Settings::load()).unwrap_or_default();
+
+    let mut items = String::new();
+
+    if let Some(fields) = settings.as_object() {
+        for (key, value) in fields {
+            if let Some(enabled) = value.as_bool() {
+                items.push_str(&format!(
+                    r#"<li><label for="{key}">{label}</label><input type="checkbox" id="{key}" {checked} onchange="abrw({{ action: 'set_setting', key: '{key}', value: this.checked }})"></li>"#,
+                    key = key,
+                    label = setting_label(key),
+                    checked = if enabled { "checked" } else { "" },
+                ));
+            }
+        }
+    }
+
+    internal_page(
+        "Settings",
+        &format!(
+            r#"<p class="darkened">Most changes apply to tabs opened from now on.</p>
+            <button onclick="abrw({{ action: 'open_settings' }})">All settings</button>
+            <ul>{}</ul>"#,
+            items
+        ),
+    )
+}
+
+fn history_page() -> String {
+    let history = History::load();
+
+    let items: String = history
+        .entries
+        .iter()
+        .rev()
+        .map(|entry| {
+            let title = if entry.title.is_empty() {
+                &entry.uri
+            } else {
+                &entry.title
+            };
+
+            format!(
+                r#"<li><a href="{uri}">{title}</a><span class="darkened">{visits}</span></li>"#,
+                uri = escape_html(&entry.uri),
+                title = escape_html(title),
+                visits = entry.visit_count,
+            )
+        })
+        .collect();
+
+    let body = if items.is_empty() {
+        r#"<p class="darkened">No history yet.</p>"#.to_string()
+    } else {
+        format!(
+            r#"<button onclick="abrw({{ action: 'clear_history' }})">Clear history</button><ul>{}</ul>"#,
+            items
+        )
+    };
+
+    internal_page("History", &body)
+}
+
+fn bookmarks_page() -> String {
+    let bookmarks = Bookmarks::load();
+
+    let items: String = bookmarks
+        .bookmarks
+        .iter()
+        .map(|bookmark| {
+            // The uri goes into a JS string inside an attribute
+            let uri_json = serde_json::to_string(&bookmark.uri).unwrap_or_default();
+
+            format!(
+                r#"<li><a href="{uri}">{title}</a><button onclick="abrw({{ action: 'remove_bookmark', uri: {uri_json} }})">Remove</button></li>"#,
+                uri = escape_html(&bookmark.uri),
+                title = escape_html(&bookmark.title),
+                uri_json = escape_html(&uri_json),
+            )
+        })
+        .collect();
+
+    let body = if items.is_empty() {
+        r#"<p class="darkened">No bookmarks yet.</p>"#.to_string()
+    } else {
+        format!("<ul>{}</ul>", items)
+    };
+
+    internal_page("Bookmarks", &body)
+}
+
+fn downloads_page() -> String {
+    let items: String = with_downloads(|downloads| {
+        downloads
+            .iter()
+            .rev()
+            .map(|record| {
+                let status = match record.status.get() {
+                    DownloadStatus::InProgress => format!(
+                        "{}%",
+                        (record.download.estimated_progress() * 100.0).round()
+                    ),
+                    DownloadStatus::Finished => "Done".to_string(),
+                    DownloadStatus::Failed => "Failed".to_string(),
+                };
+
+                let destination = record
+                    .download
+                    .destination()
+                    .map(|destination| destination.to_string())
+                    .unwrap_or_default();
+
+                format!(
+                    r#"<li><span>{uri}<br><span class="darkened">{destination}</span></span><span class="darkened">{status}</span></li>"#,
+                    uri = escape_html(&record.uri),
+                    destination = escape_html(&destination),
+                    status = status,
+                )
+            })
+            .collect()
+    });
+
+    let body = if items.is_empty() {
+        r#"<p class="darkened">No downloads in this session.</p>"#.to_string()
+    } else {
+        format!("<ul>{}</ul>", items)
+    };
+
+    internal_page("Downloads", &body)
+}
+
+fn about_page() -> String {
+    let webkit_version = unsafe {
+        format!(
+            "{}.{}.{}",
+            webkit2gtk_sys::webkit_get_major_version(),
+            webkit2gtk_sys::webkit_get_minor_version(),
+            webkit2gtk_sys::webkit_get_micro_version()
+        )
+    };
+
+    internal_page(
+        "About abrw",
+        &format!(
+            r#"<ul>
+                <li><span>Version</span><span class="darkened">{}</span></li>
+                <li><span>WebKitGTK</span><span class="darkened">{}</span></li>
+                <li><span>Source</span><a href="https://github.com/aapelix/abrw">github.com/aapelix/abrw</a></li>
+            </ul>"#,
+            env!("CARGO_PKG_VERSION"),
+            webkit_version
+        ),
+    )
+}
+
+/// Lets internal pages in `webview` send messages to the browser.
+pub fn add_internal_message_handler(webview: &WebView) {
+    let content_manager = match webview.user_content_manager() {
+        Some(content_manager) => content_manager,
+        None => return,
+    };
+
+    content_manager.register_script_message_handler(INTERNAL_MESSAGE);
+
+    let webview = webview.downgrade();
+
+    content_manager.connect_script_message_received(Some(INTERNAL_MESSAGE), move |_, result| {
+        let webview = match webview.upgrade() {
+            Some(webview) => webview,
+            None => return,
+        };
+
+        let message: serde_json::Value = match result.js_value() {
+            Some(value) => serde_json::from_str(&value.to_str()).unwrap_or(serde_json::Value::Null),
+            None => return,
+        };
+
+        // Web pages can post to any registered handler, and webview.uri()
+        // already reports an internal page while the previous page still
+        // runs, so only the token tells who is asking
+        let token = message["token"].as_str().unwrap_or_default();
+        let is_internal = webview
+            .uri()
+            .and_then(|uri| Url::parse(&uri).ok())
+            .is_some_and(|url| url.scheme() == INTERNAL_SCHEME);
+
+        if !is_internal || !page_tokens(&webview).iter().any(|issued| issued == token) {
+            println!("Ignored internal page message from {:?}", webview.uri());
+            return;
+        }
+
+        handle_internal_message(&webview, &message);
+    });
+}
+
+fn handle_internal_message(webview: &WebView, message: &serde_json::Value) {
+    match message["action"].as_str() {
+        Some("open_settings") => show_settings_window(),
+        Some("set_setting") => {
+            let key = message["key"].as_str().unwrap_or_default();
+            let previous = Settings::load();
+            let mut settings = serde_json::to_value(&previous).unwrap_or_default();
+
+            // Only existing switches can be flipped from a page
+            if let (Some(true), Some(value)) = (
+                settings.get(key).map(|v| v.is_boolean()),
+                message["value"].as_bool(),
+            ) {
+                settings[key] = serde_json::Value::Bool(value);
+
+                if let Ok(settings) = serde_json::from_value::<Settings>(settings) {
+                    apply_setting_changes(&previous, &settings);
+                    settings.save();
+                }
+            }
+        }
+        Some("search") => {
+            let query = message["query"].as_str().unwrap_or_default().trim();
+
+            if !query.is_empty() {
+                webview.load_uri(&input_to_uri(query));
+            }
+        }
+        Some("pin_tile") => {
+            let uri = message["uri"].as_str().unwrap_or_default();
+            let title = message["title"].as_str().unwrap_or_default();
+
+            // Tiles added by hand are typed like the address bar
+            pin_tile(title, &input_to_uri(uri));
+            webview.reload();
+        }
+        Some("unpin_tile") => {
+            unpin_tile(message["uri"].as_str().unwrap_or_default());
+            webview.reload();
+        }
+        Some("clear_history") => {
+            clear_history();
+            webview.reload();
+        }
+        Some("remove_bookmark") => {
+            let uri = message["uri"].as_str().unwrap_or_default();
+            let mut bookmarks = Bookmarks::load();
+
+            bookmarks.bookmarks.retain(|bookmark| bookmark.uri != uri);
+            bookmarks.save();
+            webview.reload();
+        }
+        action => println!("Unknown internal page action {:?}", action),
+    }
+}
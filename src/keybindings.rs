@@ -0,0 +1,659 @@
+use gtk::gdk::keys::{constants, Key};
+use gtk::gdk::ModifierType;
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use webkit2gtk::WebViewExt;
+
+use crate::connections::get_webview;
+use crate::devtools::toggle_devtools;
+use crate::findbar::{find_next, find_previous, show_find_bar};
+use crate::palette::show_command_palette;
+use crate::print::{print_page, show_save_as_pdf_dialog};
+use crate::reader::toggle_reader_mode;
+use crate::screenshot::{take_screenshot, ScreenshotArea};
+use crate::settings::show_settings_window;
+use crate::sidebar::toggle_vertical_tabs;
+use crate::sitesettings::set_site_setting;
+use crate::source::view_page_source;
+use crate::switcher::show_tab_switcher;
+use crate::tabs::{
+    add_tab, bookmark_all_tabs, close_tab, duplicate_tab, is_tab_pinned, move_tab_to_new_window,
+    reload_tab, set_tab_pinned,
+};
+use crate::webview::{clear_site_cookies, get_webview_setting, WebviewSetting};
+use crate::zoom::{change_zoom, reset_zoom, ZOOM_STEP};
+use crate::{create_window, BrowserWindow};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    NewTab,
+    NewWindow,
+    CloseTab,
+    FocusAddressBar,
+    NextTab,
+    PreviousTab,
+    GoBack,
+    GoForward,
+    Reload,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    Find,
+    FindNext,
+    FindPrevious,
+    Print,
+    SaveAsPdf,
+    ScreenshotVisible,
+    ScreenshotFullPage,
+    ScreenshotRegion,
+    ToggleReader,
+    ViewSource,
+    ToggleDevtools,
+    TabSwitcher,
+    CommandPalette,
+    DuplicateTab,
+    TogglePinTab,
+    MoveTabToNewWindow,
+    BookmarkAllTabs,
+    ToggleVerticalTabs,
+    ToggleAdblock,
+    ToggleJavascript,
+    ClearSiteCookies,
+    Settings,
+}
+
+impl Action {
+    pub const ALL: [Action; 34] = [
+        Action::NewTab,
+        Action::NewWindow,
+        Action::CloseTab,
+        Action::FocusAddressBar,
+        Action::NextTab,
+        Action::PreviousTab,
+        Action::GoBack,
+        Action::GoForward,
+        Action::Reload,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::ZoomReset,
+        Action::Find,
+        Action::FindNext,
+        Action::FindPrevious,
+        Action::Print,
+        Action::SaveAsPdf,
+        Action::ScreenshotVisible,
+        Action::ScreenshotFullPage,
+        Action::ScreenshotRegion,
+        Action::ToggleReader,
+        Action::ViewSource,
+        Action::ToggleDevtools,
+        Action::TabSwitcher,
+        Action::CommandPalette,
+        Action::DuplicateTab,
+        Action::TogglePinTab,
+        Action::MoveTabToNewWindow,
+        Action::BookmarkAllTabs,
+        Action::ToggleVerticalTabs,
+        Action::ToggleAdblock,
+        Action::ToggleJavascript,
+        Action::ClearSiteCookies,
+        Action::Settings,
+    ];
+
+    /// Name used for the action in keybindings.json
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::NewTab => "new_tab",
+            Action::NewWindow => "new_window",
+            Action::CloseTab => "close_tab",
+            Action::FocusAddressBar => "focus_address_bar",
+            Action::NextTab => "next_tab",
+            Action::PreviousTab => "previous_tab",
+            Action::GoBack => "go_back",
+            Action::GoForward => "go_forward",
+            Action::Reload => "reload",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::ZoomReset => "zoom_reset",
+            Action::Find => "find",
+            Action::FindNext => "find_next",
+            Action::FindPrevious => "find_previous",
+            Action::Print => "print",
+            Action::SaveAsPdf => "save_as_pdf",
+            Action::ScreenshotVisible => "screenshot_visible",
+            Action::ScreenshotFullPage => "screenshot_full_page",
+            Action::ScreenshotRegion => "screenshot_region",
+            Action::ToggleReader => "toggle_reader",
+            Action::ViewSource => "view_source",
+            Action::ToggleDevtools => "toggle_devtools",
+            Action::TabSwitcher => "tab_switcher",
+            Action::CommandPalette => "command_palette",
+            Action::DuplicateTab => "duplicate_tab",
+            Action::TogglePinTab => "toggle_pin_tab",
+            Action::MoveTabToNewWindow => "move_tab_to_new_window",
+            Action::BookmarkAllTabs => "bookmark_all_tabs",
+            Action::ToggleVerticalTabs => "toggle_vertical_tabs",
+            Action::ToggleAdblock => "toggle_adblock",
+            Action::ToggleJavascript => "toggle_javascript",
+            Action::ClearSiteCookies => "clear_site_cookies",
+            Action::Settings => "settings",
+        }
+    }
+
+    /// Name shown for the action in the command palette
+    pub fn title(&self) -> &'static str {
+        match self {
+            Action::NewTab => "New tab",
+            Action::NewWindow => "New window",
+            Action::CloseTab => "Close tab",
+            Action::FocusAddressBar => "Focus address bar",
+            Action::NextTab => "Next tab",
+            Action::PreviousTab => "Previous tab",
+            Action::GoBack => "Go back",
+            Action::GoForward => "Go forward",
+            Action::Reload => "Reload",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::ZoomReset => "Reset zoom",
+            Action::Find => "Find in page",
+            Action::FindNext => "Find next",
+            Action::FindPrevious => "Find previous",
+            Action::Print => "Print",
+            Action::SaveAsPdf => "Save page as PDF",
+            Action::ScreenshotVisible => "Screenshot visible area",
+            Action::ScreenshotFullPage => "Screenshot full page",
+            Action::ScreenshotRegion => "Screenshot region",
+            Action::ToggleReader => "Toggle reader view",
+            Action::ViewSource => "View page source",
+            Action::ToggleDevtools => "Toggle developer tools",
+            Action::TabSwitcher => "Switch tab",
+            Action::CommandPalette => "Command palette",
+            Action::DuplicateTab => "Duplicate tab",
+            Action::TogglePinTab => "Pin or unpin tab",
+            Action::MoveTabToNewWindow => "Move tab to new window",
+            Action::BookmarkAllTabs => "Bookmark all tabs",
+            Action::ToggleVerticalTabs => "Toggle vertical tabs",
+            Action::ToggleAdblock => "Toggle adblock",
+            Action::ToggleJavascript => "Toggle JavaScript for this site",
+            Action::ClearSiteCookies => "Clear cookies for this site",
+            Action::Settings => "Open settings",
+        }
+    }
+
+    fn default_shortcuts(&self) -> &'static [&'static str] {
+        match self {
+            Action::NewTab => &["Ctrl+T", "F1"],
+            Action::NewWindow => &["Ctrl+N", "F2"],
+            Action::CloseTab => &["Ctrl+W"],
+            Action::FocusAddressBar => &["Ctrl+L", "F6"],
+            Action::NextTab => &["Ctrl+Tab", "Ctrl+Page_Down"],
+            Action::PreviousTab => &["Ctrl+Shift+Tab", "Ctrl+Page_Up"],
+            Action::GoBack => &["Alt+Left"],
+            Action::GoForward => &["Alt+Right"],
+            Action::Reload => &["Ctrl+R", "F5"],
+            Action::ZoomIn => &["Ctrl+plus", "Ctrl+equal"],
+            Action::ZoomOut => &["Ctrl+minus"],
+            Action::ZoomReset => &["Ctrl+0"],
+            Action::Find => &["Ctrl+F"],
+            Action::FindNext => &["Ctrl+G", "F3"],
+            Action::FindPrevious => &["Ctrl+Shift+G", "Shift+F3"],
+            Action::Print => &["Ctrl+P"],
+            Action::SaveAsPdf => &["Ctrl+Shift+S"],
+            Action::ScreenshotVisible => &["Ctrl+Shift+X"],
+            Action::ScreenshotFullPage => &["Ctrl+Shift+F"],
+            Action::ScreenshotRegion => &["Ctrl+Shift+R"],
+            Action::ToggleReader => &["F9"],
+            Action::ViewSource => &["Ctrl+U"],
+            Action::ToggleDevtools => &["F12", "Ctrl+Shift+I"],
+            Action::TabSwitcher => &["Ctrl+Shift+A"],
+            Action::CommandPalette => &["Ctrl+Shift+P"],
+            Action::DuplicateTab
+            | Action::TogglePinTab
+            | Action::MoveTabToNewWindow
+            | Action::BookmarkAllTabs
+            | Action::ToggleVerticalTabs
+            | Action::ToggleAdblock
+            | Action::ToggleJavascript
+            | Action::ClearSiteCookies => &[],
+            Action::Settings => &["Ctrl+comma"],
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Shortcut {
+    key: Key,
+    modifiers: ModifierType,
+}
+
+impl Shortcut {
+    /// Parses shortcuts like "Ctrl+Shift+Tab", "Alt+Left" or "F5".
+    /// Keys use GDK key names, so "+" is written as "plus".
+    fn parse(text: &str) -> Option<Shortcut> {
+        let mut parts: Vec<&str> = text.split('+').map(|part| part.trim()).collect();
+        let key_name = parts.pop()?;
+
+        let mut modifiers = ModifierType::empty();
+
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "ctrl" | "control" => ModifierType::CONTROL_MASK,
+                "shift" => ModifierType::SHIFT_MASK,
+                "alt" => ModifierType::MOD1_MASK,
+                "super" => ModifierType::SUPER_MASK,
+                _ => return None,
+            };
+        }
+
+        let mut chars = key_name.chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(c), None) => Key::from_unicode(c),
+            _ => Key::from_name(key_name),
+        };
+
+        if *key == 0 || key == constants::VoidSymbol {
+            return None;
+        }
+
+        Some(Shortcut {
+            key: normalize_key(key),
+            modifiers,
+        })
+    }
+
+    fn label(&self) -> String {
+        gtk::accelerator_get_label(*self.key, self.modifiers)
+            .map(|label| label.to_string())
+            .unwrap_or_default()
+    }
+
+    fn matches(&self, key: Key, modifiers: ModifierType) -> bool {
+        if self.key != key {
+            return false;
+        }
+
+        if self.modifiers == modifiers {
+            return true;
+        }
+
+        // Symbols like "plus" are typed with shift on most layouts,
+        // so shift only counts for keys that have a distinct shifted form
+        let shift_is_typing = key.to_lower() == key.to_upper() && key != constants::Tab;
+
+        shift_is_typing
+            && !self.modifiers.contains(ModifierType::SHIFT_MASK)
+            && modifiers - ModifierType::SHIFT_MASK == self.modifiers
+    }
+}
+
+fn normalize_key(key: Key) -> Key {
+    match key {
+        constants::ISO_Left_Tab => constants::Tab,
+        _ => key.to_lower(),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    action: Action,
+    shortcut: Shortcut,
+}
+
+thread_local! {
+    static BINDINGS: RefCell<Option<Vec<Binding>>> = const { RefCell::new(None) };
+}
+
+/// User overrides from keybindings.json, e.g. `{ "new_tab": ["Ctrl+T"] }`.
+/// An action listed with no shortcuts is unbound.
+fn load_overrides() -> HashMap<String, Vec<String>> {
+    if let Ok(data) = fs::read_to_string("keybindings.json") {
+        serde_json::from_str(&data).unwrap_or_else(|err| {
+            println!("Failed to parse keybindings.json: {}", err);
+            HashMap::new()
+        })
+    } else {
+        HashMap::new()
+    }
+}
+
+fn load_bindings() -> Vec<Binding> {
+    resolve_bindings(&load_overrides())
+}
+
+/// The bindings for `overrides` on top of the default shortcuts. A shortcut
+/// claimed twice stays with the first action, overrides before defaults.
+fn resolve_bindings(overrides: &HashMap<String, Vec<String>>) -> Vec<Binding> {
+    for name in overrides.keys() {
+        if Action::from_name(name).is_none() {
+            println!("Unknown action in keybindings.json: {}", name);
+        }
+    }
+
+    // Overridden shortcuts go first, so they win conflicts with defaults
+    let mut candidates: Vec<(Action, String)> = Vec::new();
+
+    for action in Action::ALL {
+        if let Some(shortcuts) = overrides.get(action.name()) {
+            candidates.extend(shortcuts.iter().map(|text| (action, text.clone())));
+        }
+    }
+
+    for action in Action::ALL {
+        if !overrides.contains_key(action.name()) {
+            candidates.extend(
+                action
+                    .default_shortcuts()
+                    .iter()
+                    .map(|text| (action, text.to_string())),
+            );
+        }
+    }
+
+    let mut bindings: Vec<Binding> = Vec::new();
+
+    for (action, text) in candidates {
+        let shortcut = match Shortcut::parse(&text) {
+            Some(shortcut) => shortcut,
+            None => {
+                println!("Invalid shortcut for {}: {}", action.name(), text);
+                continue;
+            }
+        };
+
+        if let Some(existing) = bindings.iter().find(|binding| binding.shortcut == shortcut) {
+            println!(
+                "Keybinding conflict: {} is bound to both {} and {}, keeping {}",
+                text,
+                existing.action.name(),
+                action.name(),
+                existing.action.name()
+            );
+            continue;
+        }
+
+        bindings.push(Binding { action, shortcut });
+    }
+
+    bindings
+}
+
+fn with_bindings<T>(f: impl FnOnce(&[Binding]) -> T) -> T {
+    BINDINGS.with(|bindings| {
+        let mut bindings = bindings.borrow_mut();
+        f(bindings.get_or_insert_with(load_bindings))
+    })
+}
+
+/// The action bound to a key press, if any.
+pub fn action_for_event(event: &gtk::gdk::EventKey) -> Option<Action> {
+    let key = normalize_key(event.keyval());
+    let modifiers = event.state()
+        & (ModifierType::CONTROL_MASK
+            | ModifierType::SHIFT_MASK
+            | ModifierType::MOD1_MASK
+            | ModifierType::SUPER_MASK);
+
+    with_bindings(|bindings| {
+        bindings
+            .iter()
+            .find(|binding| binding.shortcut.matches(key, modifiers))
+            .map(|binding| binding.action)
+    })
+}
+
+/// Label of the first shortcut bound to `action`, or an empty string.
+pub fn shortcut_label(action: Action) -> String {
+    with_bindings(|bindings| {
+        bindings
+            .iter()
+            .find(|binding| binding.action == action)
+            .map(|binding| binding.shortcut.label())
+            .unwrap_or_default()
+    })
+}
+
+pub fn run_action(action: Action, browser_window: &BrowserWindow) {
+    let notebook = &browser_window.notebook;
+    let current_page = notebook.nth_page(notebook.current_page());
+
+    match action {
+        Action::NewTab => {
+            add_tab(notebook, &browser_window.search_bar, None);
+        }
+        Action::NewWindow => {
+            create_window(None);
+        }
+        Action::CloseTab => {
+            if let Some(page) = current_page {
+                close_tab(&page);
+            }
+        }
+        Action::FocusAddressBar => browser_window.search_bar.grab_focus(),
+        Action::NextTab => cycle_tab(notebook, 1),
+        Action::PreviousTab => cycle_tab(notebook, -1),
+        Action::GoBack => {
+            if let Some(webview) = get_webview(notebook) {
+                if webview.can_go_back() {
+                    webview.go_back();
+                }
+            }
+        }
+        Action::GoForward => {
+            if let Some(webview) = get_webview(notebook) {
+                if webview.can_go_forward() {
+                    webview.go_forward();
+                }
+            }
+        }
+        Action::Reload => {
+            if let Some(page) = current_page {
+                reload_tab(&page);
+            }
+        }
+        Action::ZoomIn => change_zoom(notebook, ZOOM_STEP),
+        Action::ZoomOut => change_zoom(notebook, -ZOOM_STEP),
+        Action::ZoomReset => reset_zoom(notebook),
+        Action::Find => show_find_bar(browser_window),
+        Action::FindNext => find_next(notebook),
+        Action::FindPrevious => find_previous(notebook),
+        Action::Print => {
+            if let Some(webview) = get_webview(notebook) {
+                print_page(&webview, &browser_window.window);
+            }
+        }
+        Action::SaveAsPdf => {
+            if let Some(webview) = get_webview(notebook) {
+                show_save_as_pdf_dialog(&webview, &browser_window.window);
+            }
+        }
+        Action::ScreenshotVisible | Action::ScreenshotFullPage | Action::ScreenshotRegion => {
+            let area = match action {
+                Action::ScreenshotFullPage => ScreenshotArea::FullPage,
+                Action::ScreenshotRegion => ScreenshotArea::Region,
+                _ => ScreenshotArea::Visible,
+            };
+
+            if let Some(webview) = get_webview(notebook) {
+                take_screenshot(&webview, &browser_window.window, area);
+            }
+        }
+        Action::ToggleReader => toggle_reader_mode(browser_window),
+        Action::ViewSource => {
+            if let Some(page) = current_page {
+                view_page_source(&page);
+            }
+        }
+        Action::ToggleDevtools => {
+            if let Some(page) = current_page {
+                toggle_devtools(&page);
+            }
+        }
+        Action::TabSwitcher => show_tab_switcher(&browser_window.window),
+        Action::CommandPalette => show_command_palette(browser_window),
+        Action::DuplicateTab => {
+            if let Some(page) = current_page {
+                duplicate_tab(&page);
+            }
+        }
+        Action::TogglePinTab => {
+            if let Some(page) = current_page {
+                set_tab_pinned(&page, !is_tab_pinned(&page));
+            }
+        }
+        Action::MoveTabToNewWindow => {
+            if let Some(page) = current_page {
+                move_tab_to_new_window(&page);
+            }
+        }
+        Action::BookmarkAllTabs => bookmark_all_tabs(notebook),
+        Action::ToggleVerticalTabs => toggle_vertical_tabs(),
+        Action::ToggleAdblock => {
+            let adblock_switch = &browser_window.adblock_switch;
+            adblock_switch.set_active(!adblock_switch.is_active());
+        }
+        Action::ToggleJavascript => {
+            if let Some(webview) = get_webview(notebook) {
+                let enabled =
+                    get_webview_setting(&webview, WebviewSetting::Javascript).unwrap_or_default();
+
+                set_site_setting(&webview, WebviewSetting::Javascript, !enabled);
+            }
+        }
+        Action::ClearSiteCookies => {
+            if let Some(webview) = get_webview(notebook) {
+                clear_site_cookies(&webview);
+            }
+        }
+        Action::Settings => show_settings_window(),
+    }
+}
+
+/// Moves `offset` visible tabs along, wrapping around at either end.
+fn cycle_tab(notebook: &gtk::Notebook, offset: i32) {
+    let pages: Vec<gtk::Widget> = notebook
+        .children()
+        .into_iter()
+        .filter(|page| page.is_visible())
+        .collect();
+
+    if pages.is_empty() {
+        return;
+    }
+
+    let current = notebook
+        .nth_page(notebook.current_page())
+        .and_then(|current| pages.iter().position(|page| *page == current))
+        .unwrap_or(0) as i32;
+
+    let next = (current + offset).rem_euclid(pages.len() as i32) as usize;
+    notebook.set_current_page(notebook.page_num(&pages[next]));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shortcut(text: &str) -> Shortcut {
+        Shortcut::parse(text).unwrap()
+    }
+
+    fn overrides(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(name, shortcuts)| {
+                let shortcuts = shortcuts.iter().map(|text| text.to_string()).collect();
+                (name.to_string(), shortcuts)
+            })
+            .collect()
+    }
+
+    fn actions_for(bindings: &[Binding], text: &str) -> Vec<Action> {
+        bindings
+            .iter()
+            .filter(|binding| binding.shortcut == shortcut(text))
+            .map(|binding| binding.action)
+            .collect()
+    }
+
+    #[test]
+    fn parses_modifiers_and_keys() {
+        assert_eq!(
+            shortcut("Ctrl+Shift+Tab"),
+            Shortcut {
+                key: constants::Tab,
+                modifiers: ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK,
+            }
+        );
+        assert_eq!(shortcut("Alt+Left").modifiers, ModifierType::MOD1_MASK);
+        assert_eq!(shortcut("Alt+Left").key, constants::Left);
+        assert_eq!(shortcut("F5").modifiers, ModifierType::empty());
+        assert_eq!(shortcut("Ctrl+plus").key, constants::plus);
+    }
+
+    #[test]
+    fn parsing_ignores_case_and_spaces() {
+        assert_eq!(shortcut("Ctrl+T"), shortcut("ctrl + t"));
+        assert_eq!(shortcut("Control+T"), shortcut("Ctrl+T"));
+        assert_eq!(shortcut("Shift+ISO_Left_Tab").key, constants::Tab);
+    }
+
+    #[test]
+    fn rejects_invalid_shortcuts() {
+        assert_eq!(Shortcut::parse(""), None);
+        assert_eq!(Shortcut::parse("Ctrl+"), None);
+        assert_eq!(Shortcut::parse("Hyper+T"), None);
+        assert_eq!(Shortcut::parse("Ctrl+NotAKey"), None);
+    }
+
+    #[test]
+    fn default_shortcuts_dont_conflict() {
+        let default_count: usize = Action::ALL
+            .iter()
+            .map(|action| action.default_shortcuts().len())
+            .sum();
+
+        assert_eq!(resolve_bindings(&HashMap::new()).len(), default_count);
+    }
+
+    #[test]
+    fn overrides_win_conflicts_with_defaults() {
+        let bindings = resolve_bindings(&overrides(&[("reload", &["Ctrl+T"])]));
+
+        assert_eq!(actions_for(&bindings, "Ctrl+T"), vec![Action::Reload]);
+        assert_eq!(actions_for(&bindings, "F1"), vec![Action::NewTab]);
+        assert!(actions_for(&bindings, "F5").is_empty());
+    }
+
+    #[test]
+    fn first_override_wins_conflicts_between_overrides() {
+        let bindings = resolve_bindings(&overrides(&[
+            ("new_tab", &["Ctrl+J"]),
+            ("new_window", &["Ctrl+J"]),
+        ]));
+
+        assert_eq!(actions_for(&bindings, "Ctrl+J"), vec![Action::NewTab]);
+    }
+
+    #[test]
+    fn empty_override_unbinds_action() {
+        let bindings = resolve_bindings(&overrides(&[("new_tab", &[])]));
+
+        assert!(bindings
+            .iter()
+            .all(|binding| binding.action != Action::NewTab));
+    }
+
+    #[test]
+    fn invalid_overrides_are_skipped() {
+        let bindings = resolve_bindings(&overrides(&[("find", &["Ctrl+NotAKey", "Ctrl+K"])]));
+
+        assert_eq!(actions_for(&bindings, "Ctrl+K"), vec![Action::Find]);
+        assert!(actions_for(&bindings, "Ctrl+F").is_empty());
+    }
+}
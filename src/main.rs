@@ -1,42 +1,130 @@
 extern crate gtk;
 extern crate webkit2gtk;
 
+mod bookmarks;
 mod connections;
+mod devtools;
+mod downloads;
+mod errorpage;
+mod findbar;
+mod groups;
+mod history;
+mod https;
+mod internal;
+mod keybindings;
+mod newtab;
+mod palette;
+mod permissions;
+mod popuplist;
+mod print;
+mod reader;
+mod screenshot;
+mod security;
+mod session;
 mod settings;
+mod sidebar;
+mod sitesettings;
+mod source;
+mod storage;
 mod styles;
+mod switcher;
 mod tabs;
+mod tls;
+mod vim;
 mod webview;
+mod zoom;
 
 use connections::get_webview;
-use gtk::gdk::keys::constants;
 use gtk::gdk_pixbuf::Pixbuf;
 use gtk::{glib::Propagation, prelude::*, Box, Button, Entry, Notebook};
 use gtk::{Image, Label, Popover, Switch};
-use settings::{show_settings_window, Settings};
+use settings::Settings;
+use sitesettings::{is_syncing_site_switches, set_site_setting};
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use styles::apply_css_style;
 use tabs::add_tab;
 use tokio;
-use webkit2gtk::{CookieManagerExt, WebContext, WebContextExt, WebViewExt};
-use webview::{change_webview_setting, WebviewSetting};
+use webkit2gtk::{CookieManagerExt, WebContext, WebContextExt};
+use webview::WebviewSetting;
+
+const TAB_GROUP_NAME: &str = "abrw-tabs";
+
+#[derive(Clone)]
+pub struct BrowserWindow {
+    pub window: gtk::Window,
+    pub notebook: Notebook,
+    pub search_bar: Entry,
+    pub sidebar: Box,
+    pub tab_tree: gtk::TreeView,
+    pub adblock_switch: Switch,
+    pub find_bar: findbar::FindBar,
+    pub zoom_button: Button,
+    pub site_switches: Vec<(WebviewSetting, Switch)>,
+}
 
-static WINDOW_COUNT: AtomicUsize = AtomicUsize::new(0);
+thread_local! {
+    static WINDOWS: RefCell<Vec<BrowserWindow>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn browser_windows() -> Vec<BrowserWindow> {
+    WINDOWS.with(|windows| windows.borrow().clone())
+}
+
+pub fn find_browser_window(notebook: &Notebook) -> Option<BrowserWindow> {
+    browser_windows()
+        .into_iter()
+        .find(|browser_window| &browser_window.notebook == notebook)
+}
 
 #[tokio::main]
 async fn main() {
     std::env::set_var("GDK_BACKEND", "x11");
     gtk::init().expect("Failed to initialize GTK.");
-    create_window(None);
+
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.get(1).map(|arg| arg.as_str()) == Some("--print-to-pdf") {
+        match (args.get(2), args.get(3)) {
+            (Some(uri), Some(output)) => {
+                std::process::exit(print::run_print_to_pdf(uri, Path::new(output)));
+            }
+            _ => {
+                println!("Usage: {} --print-to-pdf <url> <output.pdf>", args[0]);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    session::restore_session();
+
+    gtk::glib::timeout_add_seconds_local(60, || {
+        tabs::suspend_idle_tabs();
+        gtk::glib::ControlFlow::Continue
+    });
 
     gtk::main();
 }
 
-pub fn create_window(default_tab_url: Option<&str>) {
-    WINDOW_COUNT.fetch_add(1, Ordering::SeqCst);
+pub fn create_window(default_tab_url: Option<&str>) -> BrowserWindow {
+    let browser_window = create_empty_window();
+
+    if let Some(url) = default_tab_url {
+        browser_window.search_bar.set_text(url);
+    }
+
+    add_tab(
+        &browser_window.notebook,
+        &browser_window.search_bar,
+        default_tab_url,
+    );
 
+    browser_window
+}
+
+/// Builds a browser window without opening any tabs in it.
+pub fn create_empty_window() -> BrowserWindow {
     let adblock_enabled = Rc::new(RefCell::new(true));
 
     let provider = gtk::CssProvider::new();
@@ -103,6 +191,25 @@ pub fn create_window(default_tab_url: Option<&str>) {
 
     let search_bar = Entry::new();
 
+    // Shows the zoom of the current tab at the end of the address bar,
+    // clicking it resets the zoom
+    let zoom_button = Button::with_label("100%");
+    zoom_button.set_no_show_all(true);
+    zoom_button.set_tooltip_text(Some("Reset zoom"));
+
+    let address_bar = Box::new(gtk::Orientation::Horizontal, 0);
+    address_bar.pack_start(&search_bar, true, true, 0);
+    address_bar.pack_end(&zoom_button, false, false, 0);
+
+    apply_css_style(
+        &[address_bar.upcast_ref(), zoom_button.upcast_ref()],
+        "
+        box { background: #2a2a2a; border: 1px solid #2d2d2d; border-radius: 5px; margin-bottom: 5px; }
+        button { background: transparent; border: none; box-shadow: none; color: #888; padding: 0 8px; }
+        button:hover { color: #f1f1f1; }
+        ",
+    );
+
     control_buttons_box.set_halign(gtk::Align::Start);
 
     let menu_buttons_box = Box::new(gtk::Orientation::Horizontal, 0);
@@ -148,58 +255,44 @@ pub fn create_window(default_tab_url: Option<&str>) {
     let notebook = Notebook::new();
     notebook.set_action_widget(&new_tab_button, gtk::PackType::End);
 
+    // Tab group chips, filled in by groups::refresh_group_bar
+    let group_bar = Box::new(gtk::Orientation::Horizontal, 0);
+    notebook.set_action_widget(&group_bar, gtk::PackType::Start);
+
     new_tab_button.show();
+    group_bar.show();
 
     notebook.set_show_border(false);
     notebook.set_border_width(0);
 
-    for setting in [
-        WebviewSetting::Javascript,
-        WebviewSetting::WebGL,
-        WebviewSetting::JsClipboardAccess,
-    ]
-    .iter()
-    .cloned()
-    {
-        let toggle = create_toggle_switch(setting.clone(), {
+    let mut site_switches = Vec::new();
+
+    for setting in WebviewSetting::ALL {
+        let (toggle_box, toggle) = create_toggle_switch(setting, {
             let notebook = notebook.clone();
-            move |is_active| match get_webview(&notebook) {
-                Some(webview) => match setting {
-                    WebviewSetting::Javascript => {
-                        println!(
-                            "Javascript is now {}",
-                            if is_active { "enabled" } else { "disabled" }
-                        );
+            move |is_active| {
+                if is_syncing_site_switches() {
+                    return;
+                }
 
-                        change_webview_setting(&webview, WebviewSetting::Javascript, is_active);
-                    }
-                    WebviewSetting::WebGL => {
+                match get_webview(&notebook) {
+                    Some(webview) => {
                         println!(
-                            "WebGL is now {}",
-                            if is_active { "enabled" } else { "disabled" }
+                            "{} is now {} for this site",
+                            setting.title(),
+                            if is_active { "on" } else { "off" }
                         );
 
-                        change_webview_setting(&webview, WebviewSetting::WebGL, is_active);
+                        set_site_setting(&webview, setting, is_active);
                     }
-                    WebviewSetting::JsClipboardAccess => {
-                        println!(
-                            "Js clipboard access is now {}",
-                            if is_active { "enabled" } else { "disabled" }
-                        );
-
-                        change_webview_setting(
-                            &webview,
-                            WebviewSetting::JsClipboardAccess,
-                            is_active,
-                        );
+                    None => {
+                        println!("No webview")
                     }
-                },
-                None => {
-                    println!("No webview")
                 }
             }
         });
-        menu_popup_box.pack_start(&toggle, false, false, 5);
+        menu_popup_box.pack_start(&toggle_box, false, false, 5);
+        site_switches.push((setting, toggle));
     }
 
     menu_popup.add(&menu_popup_box);
@@ -225,44 +318,28 @@ pub fn create_window(default_tab_url: Option<&str>) {
     );
 
     top_bar.pack_start(&control_buttons_box, false, false, 0);
-    top_bar.pack_start(&search_bar, true, true, 5);
+    top_bar.pack_start(&address_bar, true, true, 5);
     top_bar.pack_end(&menu_buttons_box, false, false, 0);
 
-    match default_tab_url {
-        Some(url) => search_bar.set_text(&url),
-        None => search_bar.set_text(""),
-    }
-
     search_bar.set_halign(gtk::Align::Fill);
     search_bar.set_hexpand(true);
 
     hbox.pack_start(&top_bar, false, false, 5);
 
-    hbox.pack_start(&notebook, true, true, 0);
-    notebook.set_scrollable(true);
+    let find_bar = findbar::create_find_bar(&notebook);
+    hbox.pack_start(&find_bar.container, false, false, 0);
 
-    match default_tab_url {
-        Some(url) => add_tab(&notebook, &search_bar, Some(url)),
-        None => add_tab(&notebook, &search_bar, None),
-    }
+    let (sidebar, tab_tree) = sidebar::create_tab_sidebar(&notebook);
 
-    notebook.connect_drag_end(move |notebook, _| {
-        match get_webview(&notebook) {
-            Some(webview) => {
-                let uri = webview.uri();
+    let content_box = Box::new(gtk::Orientation::Horizontal, 0);
+    content_box.pack_start(&sidebar, false, false, 0);
+    content_box.pack_start(&notebook, true, true, 0);
 
-                match uri {
-                    Some(string) => {
-                        create_window(Some(&string));
-                    }
-                    None => {}
-                }
-            }
-            None => {
-                println!("No web view");
-            }
-        };
-    });
+    hbox.pack_start(&content_box, true, true, 0);
+    notebook.set_scrollable(true);
+
+    // Notebooks sharing a group name can drag tabs between each other
+    notebook.set_group_name(Some(TAB_GROUP_NAME));
 
     apply_css_style(
         &[
@@ -272,13 +349,12 @@ pub fn create_window(default_tab_url: Option<&str>) {
         ],
         "
         box { background: #202020; }
-        entry { background: #2a2a2a; border-color: #2d2d2d; margin-bottom: 5px; }
+        entry { background: transparent; border: none; box-shadow: none; }
         notebook header.top { background: #202020; box-shadow: none; }
         notebook header.top action-widget { background: #2a2a2a; padding: 5px; box-shadow: none; }
         notebook header.top tabs { background: #202020; }
         notebook header.top tabs tab {
             min-height: 15px;
-            min-width: 100px;
             background: transparent;
             border: none;
             border-radius: 7px;
@@ -300,72 +376,85 @@ pub fn create_window(default_tab_url: Option<&str>) {
     connections::refresh_button_clicked(&notebook, &refresh_button);
     connections::new_tab_button_clicked(&notebook, &new_tab_button, &search_bar);
     connections::search_entry_activate(&search_bar, &notebook);
-    connections::notebook_switch_page(&notebook, &search_bar, menu_popup_box);
+    connections::zoom_button_clicked(&notebook, &zoom_button);
+    connections::address_bar_icon_pressed(&notebook, &search_bar);
+    vim::connect_prompt_focus_out(&search_bar, &notebook);
+    connections::notebook_switch_page(&notebook, &search_bar);
     connections::settings_button_clicked(&settings_button, &notebook, &search_bar);
     connections::menu_button_clicked(&menu_popup, &menu_button);
     connections::adblock_toggle(&adblock_toggle, adblock_enabled, &notebook);
+    connections::notebook_page_added(&notebook);
+    connections::notebook_page_reordered(&notebook);
+    connections::notebook_create_window(&notebook);
+    connections::notebook_session_tracking(&notebook);
+
+    let browser_window = BrowserWindow {
+        window: window.clone(),
+        notebook: notebook.clone(),
+        search_bar: search_bar.clone(),
+        sidebar,
+        tab_tree,
+        adblock_switch: adblock_toggle.clone(),
+        find_bar,
+        zoom_button,
+        site_switches,
+    };
 
-    window.connect_key_press_event(move |_, key| {
-        let keyval = key.keyval();
+    WINDOWS.with(|windows| windows.borrow_mut().push(browser_window.clone()));
 
+    window.connect_key_press_event(move |_, key| {
         // for debugging
-        // let state = key.state();
-        // println!("Key: {:?}, State: {:?}", keyval, state);
-
-        match keyval {
-            constants::F1 => {
-                add_tab(&notebook, &search_bar, None);
-                Propagation::Stop
-            }
+        // println!("Key: {:?}, State: {:?}", key.keyval(), key.state());
 
-            constants::F2 => {
-                create_window(None);
-                Propagation::Stop
-            }
+        let browser_window = match find_browser_window(&notebook) {
+            Some(browser_window) => browser_window,
+            None => return Propagation::Proceed,
+        };
 
-            constants::F12 => {
-                show_settings_window();
+        match keybindings::action_for_event(key) {
+            Some(action) => {
+                keybindings::run_action(action, &browser_window);
                 Propagation::Stop
             }
-
-            _ => Propagation::Proceed,
+            None if vim::handle_key(&browser_window, key) => Propagation::Stop,
+            None => Propagation::Proceed,
         }
     });
 
-    window.connect_delete_event(move |_, _| {
-        WINDOW_COUNT.fetch_sub(1, Ordering::SeqCst);
-
-        if WINDOW_COUNT.load(Ordering::SeqCst) == 0 {
+    window.connect_delete_event(move |window, _| {
+        if browser_windows().len() == 1 {
+            session::save_session();
             gtk::main_quit();
-            Propagation::Stop
-        } else {
-            Propagation::Proceed
+            return Propagation::Stop;
         }
+
+        WINDOWS.with(|windows| {
+            windows
+                .borrow_mut()
+                .retain(|browser_window| &browser_window.window != window)
+        });
+        session::save_session();
+
+        Propagation::Proceed
     });
 
     // Show all widgets
     window.show_all();
+
+    sidebar::apply_tab_layout(&browser_window, Settings::load().vertical_tabs);
+
+    browser_window
 }
 
-fn create_toggle_switch<F>(setting: WebviewSetting, callback: F) -> Box
+fn create_toggle_switch<F>(setting: WebviewSetting, callback: F) -> (Box, Switch)
 where
     F: Fn(bool) + 'static,
 {
     let menu_popup_box = Box::new(gtk::Orientation::Horizontal, 5);
-    let settings = Settings::load();
-
-    let (label_text, toggle_state) = match setting {
-        WebviewSetting::Javascript => ("Javascript enabled", settings.enable_javascript),
-        WebviewSetting::WebGL => ("WebGL enabled", settings.enable_webgl),
-        WebviewSetting::JsClipboardAccess => (
-            "Js Can access clipboard",
-            settings.javascript_can_access_clipboard,
-        ),
-    };
 
-    let toggle_label = Label::new(Some(label_text));
+    let toggle_label = Label::new(Some(setting.title()));
     let toggle = Switch::new();
-    toggle.set_active(toggle_state);
+    toggle.set_active(setting.default_value(&Settings::load()));
 
     toggle.connect_active_notify(move |switch| {
         let is_active = switch.is_active();
@@ -375,7 +464,7 @@ where
     menu_popup_box.pack_start(&toggle_label, false, false, 5);
     menu_popup_box.pack_end(&toggle, false, false, 5);
 
-    menu_popup_box
+    (menu_popup_box, toggle)
 }
 
 pub fn create_button_with_icon(icon: &str) -> Button {
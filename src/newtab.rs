@@ -0,0 +1,357 @@
+use gtk::gio::MemoryInputStream;
+use gtk::glib::Bytes;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use url::Url;
+use webkit2gtk::{
+    FaviconDatabaseExt, URISchemeRequest, URISchemeRequestExt, WebContextExt, WebViewExt,
+};
+
+use crate::bookmarks::Bookmark;
+use crate::connections::input_to_uri;
+use crate::history::History;
+use crate::internal::{INTERNAL_PAGE_SCRIPT, NEWTAB_URI};
+use crate::reader::escape_html;
+use crate::settings::Settings;
+use crate::storage::{load_json, save_json};
+
+/// Tiles shown on the new tab page, pinned ones first
+const MAX_TILES: usize = 12;
+
+/// Sites the user pinned to the new tab page.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SpeedDial {
+    pub pinned: Vec<Bookmark>,
+}
+
+impl SpeedDial {
+    pub fn save(&self) {
+        save_json("speeddial.json", self);
+    }
+
+    pub fn load() -> SpeedDial {
+        load_json("speeddial.json")
+    }
+}
+
+fn uri_host(uri: &str) -> String {
+    Url::parse(uri)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_default()
+}
+
+pub fn pin_tile(title: &str, uri: &str) {
+    let mut speed_dial = SpeedDial::load();
+
+    if speed_dial.pinned.iter().any(|tile| tile.uri == uri) {
+        return;
+    }
+
+    speed_dial.pinned.push(Bookmark {
+        title: if title.is_empty() {
+            uri_host(uri)
+        } else {
+            title.to_string()
+        },
+        uri: uri.to_string(),
+    });
+    speed_dial.save();
+}
+
+pub fn unpin_tile(uri: &str) {
+    let mut speed_dial = SpeedDial::load();
+    speed_dial.pinned.retain(|tile| tile.uri != uri);
+    speed_dial.save();
+}
+
+/// Turns a setting that may be a URL, a local path or an address typed like
+/// in the address bar into a URL.
+fn setting_to_uri(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    if value.is_empty() {
+        return None;
+    }
+
+    if let Ok(url) = Url::parse(value) {
+        return Some(url.to_string());
+    }
+
+    // Absolute paths, or files relative to the working directory
+    let path = Path::new(value);
+
+    if path.is_absolute() || path.exists() {
+        let path = std::env::current_dir().ok()?.join(path);
+        return Url::from_file_path(path).ok().map(|url| url.to_string());
+    }
+
+    Some(input_to_uri(value))
+}
+
+/// Quotes `value` as a CSS string. Anything that could end the string or
+/// the style element is written as an escape.
+fn css_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in value.chars() {
+        if c.is_ascii_alphanumeric() || "/:.,_-?=#%+~;@!$()*".contains(c) {
+            quoted.push(c);
+        } else {
+            quoted.push_str(&format!("\\{:x} ", c as u32));
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// What new tabs open: the page configured in settings, or the speed dial.
+pub fn new_tab_uri() -> String {
+    setting_to_uri(&Settings::load().newtab_url).unwrap_or_else(|| NEWTAB_URI.to_string())
+}
+
+/// Pinned sites, then the most visited sites not pinned yet, one per host.
+fn tiles() -> Vec<(Bookmark, bool)> {
+    let pinned = SpeedDial::load().pinned;
+
+    let mut hosts: Vec<String> = pinned.iter().map(|tile| uri_host(&tile.uri)).collect();
+    let mut tiles: Vec<(Bookmark, bool)> = pinned.into_iter().map(|tile| (tile, true)).collect();
+
+    let mut history = History::load().entries;
+    history.sort_by_key(|entry| std::cmp::Reverse(entry.visit_count));
+
+    for entry in history {
+        if tiles.len() >= MAX_TILES {
+            break;
+        }
+
+        let host = uri_host(&entry.uri);
+
+        if hosts.contains(&host) {
+            continue;
+        }
+
+        hosts.push(host.clone());
+        tiles.push((
+            Bookmark {
+                title: if entry.title.is_empty() {
+                    host
+                } else {
+                    entry.title
+                },
+                uri: entry.uri,
+            },
+            false,
+        ));
+    }
+
+    tiles
+}
+
+fn tile_html(tile: &Bookmark, pinned: bool) -> String {
+    // Favicons come from the ones WebKit kept for visited pages, asking the
+    // sites for them would tell every one of them what else is on the page
+    let mut favicon = Url::parse("abrw://favicon").expect("Failed to parse favicon URI");
+    favicon.query_pairs_mut().append_pair("uri", &tile.uri);
+
+    // Goes into a JS call inside an attribute
+    let message = serde_json::json!({
+        "action": if pinned { "unpin_tile" } else { "pin_tile" },
+        "uri": tile.uri,
+        "title": tile.title,
+    });
+
+    format!(
+        r#"<div class="tile">
+            <a href="{uri}">
+                <img src="{favicon}" onerror="this.style.visibility = 'hidden'">
+                <span>{title}</span>
+            </a>
+            <button title="{button_title}" onclick="abrw({message})">{button}</button>
+        </div>"#,
+        uri = escape_html(&tile.uri),
+        favicon = escape_html(favicon.as_str()),
+        title = escape_html(&tile.title),
+        button_title = if pinned { "Unpin" } else { "Pin" },
+        message = escape_html(&message.to_string()),
+        button = if pinned { "x" } else { "+" },
+    )
+}
+
+pub fn newtab_page() -> String {
+    let tiles: String = tiles()
+        .iter()
+        .map(|(tile, pinned)| tile_html(tile, *pinned))
+        .collect();
+
+    let background = setting_to_uri(&Settings::load().newtab_background)
+        .map(|uri| format!("background-image: url({});", css_string(&uri)))
+        .unwrap_or_default();
+
+    format!(
+        r##"
+    <!DOCTYPE html>
+    <html lang="en">
+    <head>
+        <meta charset="UTF-8">
+        <meta name="viewport" content="width=device-width, initial-scale=1.0">
+        <title>New tab</title>
+        <style>
+            body, html {{
+                height: 100%;
+                margin: 0;
+                display: flex;
+                justify-content: center;
+                align-items: center;
+                background-color: #202020;
+                background-size: cover;
+                background-position: center;
+                color: #f1f1f1;
+                font-family: "Hack", sans-serif;
+                {background}
+            }}
+            .container {{
+                text-align: center;
+                width: 640px;
+            }}
+            form input {{
+                width: 100%;
+                box-sizing: border-box;
+                padding: 10px 15px;
+                border: none;
+                border-radius: 7px;
+                background: #2a2a2a;
+                color: #f1f1f1;
+                font-size: 16px;
+                outline: none;
+            }}
+            .tiles {{
+                display: grid;
+                grid-template-columns: repeat(4, 1fr);
+                gap: 10px;
+                margin-top: 20px;
+            }}
+            .tile {{
+                position: relative;
+                background: #2a2a2a;
+                border-radius: 7px;
+            }}
+            .tile:hover {{
+                background: #333;
+            }}
+            .tile a {{
+                display: flex;
+                flex-direction: column;
+                align-items: center;
+                gap: 8px;
+                padding: 15px 10px;
+                color: #f1f1f1;
+                text-decoration: none;
+            }}
+            .tile img {{
+                width: 24px;
+                height: 24px;
+            }}
+            .tile span {{
+                max-width: 100%;
+                overflow: hidden;
+                white-space: nowrap;
+                text-overflow: ellipsis;
+                font-size: 13px;
+            }}
+            .tile button {{
+                position: absolute;
+                top: 2px;
+                right: 2px;
+                display: none;
+                background: transparent;
+                border: none;
+                color: #888;
+                cursor: pointer;
+            }}
+            .tile:hover button {{
+                display: block;
+            }}
+            .add {{
+                display: flex;
+                align-items: center;
+                justify-content: center;
+                min-height: 80px;
+                color: #888;
+                cursor: pointer;
+            }}
+        </style>
+        <script>{script}</script>
+    </head>
+    <body>
+        <div class="container">
+            <h1>aapelix/abrw</h1>
+            <form onsubmit="abrw({{ action: 'search', query: this.query.value }}); return false;">
+                <input name="query" placeholder="Search or enter address" autofocus>
+            </form>
+            <div class="tiles">
+                {tiles}
+                <div class="tile add" title="Pin a site" onclick="const uri = prompt('Address'); if (uri) abrw({{ action: 'pin_tile', uri, title: '' }});">+</div>
+            </div>
+        </div>
+    </body>
+    </html>
+    "##,
+        background = background,
+        script = INTERNAL_PAGE_SCRIPT,
+        tiles = tiles,
+    )
+}
+
+/// Answers an abrw://favicon request with the favicon WebKit has for the
+/// page in its `uri` parameter, as a PNG.
+pub fn serve_favicon(request: &URISchemeRequest) {
+    let uri = request
+        .uri()
+        .and_then(|uri| Url::parse(&uri).ok())
+        .and_then(|url| {
+            url.query_pairs()
+                .find(|(key, _)| key == "uri")
+                .map(|(_, value)| value.to_string())
+        })
+        .unwrap_or_default();
+
+    let favicon_database = match request
+        .web_view()
+        .and_then(|webview| webview.context())
+        .and_then(|context| context.favicon_database())
+    {
+        Some(favicon_database) => favicon_database,
+        None => {
+            finish_without_favicon(request);
+            return;
+        }
+    };
+
+    let request = request.clone();
+
+    favicon_database.favicon(&uri, gtk::gio::Cancellable::NONE, move |result| {
+        let png = result
+            .ok()
+            .and_then(|surface| gtk::cairo::ImageSurface::try_from(surface).ok())
+            .and_then(|surface| {
+                gtk::gdk::pixbuf_get_from_surface(&surface, 0, 0, surface.width(), surface.height())
+            })
+            .and_then(|pixbuf| pixbuf.save_to_bufferv("png", &[]).ok());
+
+        match png {
+            Some(png) => {
+                let bytes = Bytes::from_owned(png);
+                let stream = MemoryInputStream::from_bytes(&bytes);
+                request.finish(&stream, bytes.len() as i64, Some("image/png"));
+            }
+            None => finish_without_favicon(&request),
+        }
+    });
+}
+
+fn finish_without_favicon(request: &URISchemeRequest) {
+    let mut error = gtk::glib::Error::new(gtk::gio::IOErrorEnum::NotFound, "No favicon");
+    request.finish_error(&mut error);
+}
@@ -0,0 +1,47 @@
+use gtk::prelude::*;
+use gtk::{Box, Label};
+
+use crate::keybindings::{run_action, shortcut_label, Action};
+use crate::popuplist::show_popup_list;
+use crate::styles::apply_css_style;
+use crate::switcher::fuzzy_score;
+use crate::BrowserWindow;
+
+/// Popup listing every browser action with its shortcut, filtered as you type.
+pub fn show_command_palette(browser_window: &BrowserWindow) {
+    // The palette can't run itself
+    let actions: Vec<(Action, String)> = Action::ALL
+        .into_iter()
+        .filter(|action| *action != Action::CommandPalette)
+        .map(|action| (action, shortcut_label(action)))
+        .collect();
+
+    let browser_window_clone = browser_window.clone();
+
+    show_popup_list(
+        &browser_window.window,
+        "Command palette",
+        "Run a command",
+        actions,
+        |query, (action, _)| fuzzy_score(query, action.title()),
+        |(action, shortcut)| create_palette_row(action.title(), shortcut),
+        move |(action, _)| run_action(*action, &browser_window_clone),
+    );
+}
+
+fn create_palette_row(title: &str, shortcut: &str) -> Box {
+    let row_box = Box::new(gtk::Orientation::Horizontal, 5);
+
+    let title_label = Label::new(Some(title));
+    let shortcut_label = Label::new(Some(shortcut));
+
+    title_label.set_halign(gtk::Align::Start);
+    shortcut_label.set_halign(gtk::Align::End);
+
+    apply_css_style(&[shortcut_label.upcast_ref()], "label { color: #888; }");
+
+    row_box.pack_start(&title_label, false, false, 0);
+    row_box.pack_end(&shortcut_label, false, false, 0);
+
+    row_box
+}
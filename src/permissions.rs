@@ -0,0 +1,338 @@
+use gtk::prelude::*;
+use gtk::{Box, Button, CheckButton, Label, Orientation};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::rc::Rc;
+use url::Url;
+use webkit2gtk::{
+    DeviceInfoPermissionRequest, GeolocationPermissionRequest, LoadEvent,
+    NotificationPermissionRequest, PermissionRequest, PermissionRequestExt,
+    PointerLockPermissionRequest, UserMediaPermissionRequest, UserMediaPermissionRequestExt,
+    WebView, WebViewExt,
+};
+
+use crate::settings::{create_settings_list, SettingsListRow};
+use crate::storage::{load_json, save_json};
+use crate::styles::apply_css_style;
+use crate::tabs::TabState;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PermissionKind {
+    Geolocation,
+    Notifications,
+    Camera,
+    Microphone,
+    /// Listing the camera and microphone devices, without using them
+    MediaDevices,
+    PointerLock,
+}
+
+impl PermissionKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            PermissionKind::Geolocation => "Location",
+            PermissionKind::Notifications => "Notifications",
+            PermissionKind::Camera => "Camera",
+            PermissionKind::Microphone => "Microphone",
+            PermissionKind::MediaDevices => "Media devices",
+            PermissionKind::PointerLock => "Pointer lock",
+        }
+    }
+
+    fn question(&self) -> &'static str {
+        match self {
+            PermissionKind::Geolocation => "know your location",
+            PermissionKind::Notifications => "show notifications",
+            PermissionKind::Camera => "use your camera",
+            PermissionKind::Microphone => "use your microphone",
+            PermissionKind::MediaDevices => "see your cameras and microphones",
+            PermissionKind::PointerLock => "hide and lock your mouse pointer",
+        }
+    }
+}
+
+/// Remembered permission decisions, as allowed or not by kind for each origin.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SitePermissions {
+    pub origins: HashMap<String, HashMap<PermissionKind, bool>>,
+}
+
+impl SitePermissions {
+    pub fn save(&self) {
+        save_json("site_permissions.json", self);
+    }
+
+    pub fn load() -> SitePermissions {
+        load_json("site_permissions.json")
+    }
+
+    /// The remembered decision for all of `kinds`. Blocking one of them
+    /// blocks the request, allowing needs all of them allowed.
+    fn decision(&self, origin: &str, kinds: &[PermissionKind]) -> Option<bool> {
+        let decisions = self.origins.get(origin)?;
+
+        if kinds.iter().any(|kind| decisions.get(kind) == Some(&false)) {
+            return Some(false);
+        }
+
+        if kinds.iter().all(|kind| decisions.get(kind) == Some(&true)) {
+            return Some(true);
+        }
+
+        None
+    }
+}
+
+/// The origin permissions are remembered for, like `https://example.com`.
+pub fn page_origin(uri: &str) -> Option<String> {
+    let origin = Url::parse(uri).ok()?.origin();
+
+    // Opaque origins (data:, about: and the like) can't be told apart
+    if !origin.is_tuple() {
+        return None;
+    }
+
+    Some(origin.ascii_serialization())
+}
+
+/// What a request asks for, or `None` for requests left to WebKit.
+///
+/// Clipboard reads can't be asked about: webkit2gtk 2.0 has no permission
+/// request for them, so they follow the clipboard setting in the shield
+/// popover and site overrides instead.
+fn request_kinds(request: &PermissionRequest) -> Option<Vec<PermissionKind>> {
+    if request.is::<GeolocationPermissionRequest>() {
+        return Some(vec![PermissionKind::Geolocation]);
+    }
+
+    if request.is::<NotificationPermissionRequest>() {
+        return Some(vec![PermissionKind::Notifications]);
+    }
+
+    if request.is::<DeviceInfoPermissionRequest>() {
+        return Some(vec![PermissionKind::MediaDevices]);
+    }
+
+    if request.is::<PointerLockPermissionRequest>() {
+        return Some(vec![PermissionKind::PointerLock]);
+    }
+
+    if let Some(media) = request.downcast_ref::<UserMediaPermissionRequest>() {
+        let mut kinds = Vec::new();
+
+        if media.is_for_video_device() {
+            kinds.push(PermissionKind::Camera);
+        }
+        if media.is_for_audio_device() {
+            kinds.push(PermissionKind::Microphone);
+        }
+
+        return Some(kinds);
+    }
+
+    None
+}
+
+fn remember_decision(origin: &str, kinds: &[PermissionKind], allowed: bool) {
+    let mut permissions = SitePermissions::load();
+    let decisions = permissions.origins.entry(origin.to_string()).or_default();
+
+    for kind in kinds {
+        decisions.insert(*kind, allowed);
+    }
+
+    permissions.save();
+}
+
+fn dismiss_permission_prompts(state: &TabState) {
+    for prompt in state.permission_prompts.take() {
+        // Dropping the request without an answer denies it
+        unsafe { prompt.destroy() };
+    }
+}
+
+/// Asks about permission requests from the page in `webview` in a bar above
+/// it, unless the origin has a remembered decision.
+pub fn connect_permission_requests(webview: &WebView, state: Rc<TabState>) {
+    webview.connect_load_changed({
+        let state = state.clone();
+        move |_, event| {
+            if event == LoadEvent::Committed {
+                dismiss_permission_prompts(&state);
+            }
+        }
+    });
+
+    webview.connect_destroy({
+        let state = state.clone();
+        move |_| dismiss_permission_prompts(&state)
+    });
+
+    webview.connect_permission_request(move |webview, request| {
+        let kinds = match request_kinds(request) {
+            Some(kinds) if !kinds.is_empty() => kinds,
+            _ => return false,
+        };
+
+        // WebKit doesn't say which frame asked. Frames from other origins
+        // can only ask when the page allowed them to with the iframe's
+        // `allow` attribute, so the page's origin is the one that decides,
+        // like in other browsers.
+        let origin = match webview.uri().as_deref().and_then(page_origin) {
+            Some(origin) => origin,
+            None => {
+                request.deny();
+                return true;
+            }
+        };
+
+        match SitePermissions::load().decision(&origin, &kinds) {
+            Some(true) => request.allow(),
+            Some(false) => request.deny(),
+            None => show_permission_prompt(webview, &state, request, &origin, kinds),
+        }
+
+        true
+    });
+}
+
+fn show_permission_prompt(
+    webview: &WebView,
+    state: &Rc<TabState>,
+    request: &PermissionRequest,
+    origin: &str,
+    kinds: Vec<PermissionKind>,
+) {
+    let page = match webview
+        .parent()
+        .and_then(|page| page.downcast::<Box>().ok())
+    {
+        Some(page) => page,
+        None => {
+            request.deny();
+            return;
+        }
+    };
+
+    let question = kinds
+        .iter()
+        .map(|kind| kind.question())
+        .collect::<Vec<_>>()
+        .join(" and ");
+
+    let prompt = Box::new(Orientation::Horizontal, 5);
+    let label = Label::new(Some(&format!("{} wants to {}", origin, question)));
+    label.set_tooltip_text(Some(&format!(
+        "Asked by {} or a frame it embeds and allowed to ask",
+        origin
+    )));
+    let remember_check = CheckButton::with_label("Remember");
+    let allow_button = Button::with_label("Allow");
+    let block_button = Button::with_label("Block");
+
+    label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+
+    prompt.pack_start(&label, false, false, 10);
+    prompt.pack_end(&block_button, false, false, 5);
+    prompt.pack_end(&allow_button, false, false, 0);
+    prompt.pack_end(&remember_check, false, false, 5);
+
+    apply_css_style(
+        &[
+            prompt.upcast_ref(),
+            label.upcast_ref(),
+            remember_check.upcast_ref(),
+            allow_button.upcast_ref(),
+            block_button.upcast_ref(),
+        ],
+        "
+        box { background: #2a2a2a; padding: 3px 0; }
+        label { color: #f1f1f1; }
+        checkbutton { color: #888; }
+        button { background: #202020; border: none; box-shadow: none; color: #f1f1f1; border-radius: 7px; }
+        button:hover { background: #1a1a1a; }
+        ",
+    );
+
+    page.pack_start(&prompt, false, false, 0);
+    page.reorder_child(&prompt, 0);
+    prompt.show_all();
+
+    state.permission_prompts.borrow_mut().push(prompt.clone());
+
+    for (button, allowed) in [(allow_button, true), (block_button, false)] {
+        let request = request.clone();
+        let origin = origin.to_string();
+        let kinds = kinds.clone();
+        let remember_check = remember_check.clone();
+        let prompt = prompt.clone();
+        let state = state.clone();
+
+        button.connect_clicked(move |_| {
+            if allowed {
+                request.allow();
+            } else {
+                request.deny();
+            }
+
+            if remember_check.is_active() {
+                remember_decision(&origin, &kinds, allowed);
+            }
+
+            state
+                .permission_prompts
+                .borrow_mut()
+                .retain(|other| *other != prompt);
+            unsafe { prompt.destroy() };
+        });
+    }
+}
+
+/// Lists the remembered permission decisions in the settings window, each
+/// with a button to forget it.
+pub fn create_site_permissions_section(parent_box: &Box) {
+    let mut entries: Vec<(String, PermissionKind, bool)> = SitePermissions::load()
+        .origins
+        .into_iter()
+        .flat_map(|(origin, decisions)| {
+            decisions
+                .into_iter()
+                .map(move |(kind, allowed)| (origin.clone(), kind, allowed))
+        })
+        .collect();
+    entries.sort();
+
+    let rows = entries
+        .into_iter()
+        .map(|(origin, kind, allowed)| SettingsListRow {
+            label: origin.clone(),
+            detail: Some(format!(
+                "{}: {}",
+                kind.title(),
+                if allowed { "Allowed" } else { "Blocked" }
+            )),
+            key: (origin, kind),
+        })
+        .collect();
+
+    create_settings_list(
+        parent_box,
+        "Site permissions",
+        "No site permissions",
+        ("Remove", None),
+        rows,
+        |(origin, kind)| {
+            let mut permissions = SitePermissions::load();
+
+            if let Some(decisions) = permissions.origins.get_mut(origin) {
+                decisions.remove(kind);
+
+                if decisions.is_empty() {
+                    permissions.origins.remove(origin);
+                }
+            }
+
+            permissions.save();
+        },
+    );
+}
@@ -0,0 +1,170 @@
+use gtk::gdk::keys::constants;
+use gtk::glib::Propagation;
+use gtk::prelude::*;
+use gtk::{Box, Entry, ListBox, ScrolledWindow, Window, WindowType};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::styles::apply_css_style;
+
+/// Popup listing `items`, filtered and ranked by `score` as you type. Items
+/// scored `None` are left out, equal scores keep the order of `items`.
+/// Used by the tab switcher and the command palette.
+pub fn show_popup_list<T, S, R, A>(
+    parent: &Window,
+    title: &str,
+    placeholder: &str,
+    items: Vec<T>,
+    score: S,
+    create_row: R,
+    activate: A,
+) where
+    T: Clone + 'static,
+    S: Fn(&str, &T) -> Option<i32> + 'static,
+    R: Fn(&T) -> Box + 'static,
+    A: Fn(&T) + 'static,
+{
+    let window = Window::new(WindowType::Toplevel);
+    window.set_title(title);
+    window.set_transient_for(Some(parent));
+    window.set_modal(true);
+    window.set_decorated(false);
+    window.set_default_size(600, 400);
+    window.set_position(gtk::WindowPosition::CenterOnParent);
+
+    let vbox = Box::new(gtk::Orientation::Vertical, 0);
+    let filter_entry = Entry::new();
+    filter_entry.set_placeholder_text(Some(placeholder));
+
+    let list_box = ListBox::new();
+    list_box.set_selection_mode(gtk::SelectionMode::Browse);
+
+    let scrolled_window = ScrolledWindow::new(gtk::Adjustment::NONE, gtk::Adjustment::NONE);
+    scrolled_window.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+    scrolled_window.add(&list_box);
+
+    vbox.pack_start(&filter_entry, false, false, 5);
+    vbox.pack_start(&scrolled_window, true, true, 0);
+    window.add(&vbox);
+
+    apply_css_style(
+        &[
+            vbox.upcast_ref(),
+            filter_entry.upcast_ref(),
+            list_box.upcast_ref(),
+        ],
+        "
+        box { background: #202020; }
+        entry { background: #2a2a2a; border-color: #2d2d2d; margin: 5px; }
+        list { background: #202020; }
+        row { padding: 5px; }
+        row:selected { background: #2a2a2a; }
+        ",
+    );
+
+    // Items currently shown in the list, in row order
+    let shown_items = Rc::new(RefCell::new(Vec::<T>::new()));
+
+    let refresh = {
+        let list_box = list_box.clone();
+        let shown_items = shown_items.clone();
+
+        move |query: &str| {
+            for row in list_box.children() {
+                list_box.remove(&row);
+            }
+
+            let mut matches: Vec<(i32, T)> = items
+                .iter()
+                .filter_map(|item| Some((score(query, item)?, item.clone())))
+                .collect();
+
+            // Stable sort keeps the item order for equal scores
+            matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+            for (_, item) in matches.iter() {
+                list_box.add(&create_row(item));
+            }
+
+            *shown_items.borrow_mut() = matches.into_iter().map(|(_, item)| item).collect();
+
+            list_box.select_row(list_box.row_at_index(0).as_ref());
+            list_box.show_all();
+        }
+    };
+
+    refresh("");
+
+    filter_entry.connect_changed(move |entry| refresh(&entry.text()));
+
+    let activate_row = {
+        let window = window.clone();
+        let activate = Rc::new(activate);
+
+        move |index: i32| {
+            let item = shown_items.borrow().get(index as usize).cloned();
+
+            // Close first, activating may open windows or dialogs of its own
+            window.close();
+
+            if let Some(item) = item {
+                activate(&item);
+            }
+        }
+    };
+
+    list_box.connect_row_activated({
+        let activate_row = activate_row.clone();
+        move |_, row| activate_row(row.index())
+    });
+
+    filter_entry.connect_activate({
+        let list_box = list_box.clone();
+
+        move |_| {
+            if let Some(row) = list_box.selected_row() {
+                activate_row(row.index());
+            }
+        }
+    });
+
+    filter_entry.connect_key_press_event({
+        let list_box = list_box.clone();
+        let scrolled_window = scrolled_window.clone();
+
+        move |_, key| {
+            let step = match key.keyval() {
+                constants::Down => 1,
+                constants::Up => -1,
+                _ => return Propagation::Proceed,
+            };
+
+            let index = list_box.selected_row().map_or(0, |row| row.index() + step);
+
+            // Focus stays in the entry so typing keeps filtering
+            if let Some(row) = list_box.row_at_index(index) {
+                list_box.select_row(Some(&row));
+
+                let allocation = row.allocation();
+                scrolled_window.vadjustment().clamp_page(
+                    allocation.y() as f64,
+                    (allocation.y() + allocation.height()) as f64,
+                );
+            }
+
+            Propagation::Stop
+        }
+    });
+
+    window.connect_key_press_event(|window, key| {
+        if key.keyval() == constants::Escape {
+            window.close();
+            return Propagation::Stop;
+        }
+
+        Propagation::Proceed
+    });
+
+    window.show_all();
+    filter_entry.grab_focus();
+}
@@ -0,0 +1,155 @@
+use gtk::prelude::*;
+use gtk::{FileChooserAction, FileChooserNative, PrintSettings, ResponseType, Window};
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use url::Url;
+use webkit2gtk::{LoadEvent, PrintOperation, PrintOperationExt, WebView, WebViewExt};
+
+use crate::webview::create_webview;
+
+/// Opens the GTK print dialog for the page in `webview`.
+pub fn print_page(webview: &WebView, parent: &Window) {
+    let print_operation = PrintOperation::new(webview);
+
+    print_operation.connect_failed(|_, error| {
+        println!("Printing failed: {}", error);
+    });
+
+    print_operation.run_dialog(Some(parent));
+}
+
+/// Prints the page in `webview` to a PDF file without showing any dialog.
+pub fn save_page_as_pdf<F>(webview: &WebView, path: &Path, on_done: F)
+where
+    F: Fn(Result<(), String>) + 'static,
+{
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .unwrap_or_else(|_| PathBuf::from("/"))
+            .join(path)
+    };
+
+    let output_uri = match Url::from_file_path(&path) {
+        Ok(output_uri) => output_uri,
+        Err(()) => {
+            on_done(Err(format!("Invalid output path {}", path.display())));
+            return;
+        }
+    };
+
+    let print_settings = PrintSettings::new();
+    print_settings.set_printer("Print to File");
+    print_settings.set(gtk::PRINT_SETTINGS_OUTPUT_FILE_FORMAT, Some("pdf"));
+    print_settings.set(gtk::PRINT_SETTINGS_OUTPUT_URI, Some(output_uri.as_str()));
+
+    let print_operation = PrintOperation::new(webview);
+    print_operation.set_print_settings(&print_settings);
+
+    // "finished" follows "failed" too, so only the first result is reported
+    let on_done = Rc::new(on_done);
+    let reported = Rc::new(Cell::new(false));
+
+    print_operation.connect_failed({
+        let on_done = on_done.clone();
+        let reported = reported.clone();
+        move |_, error| {
+            if !reported.replace(true) {
+                on_done(Err(error.to_string()));
+            }
+        }
+    });
+
+    print_operation.connect_finished(move |_| {
+        if !reported.replace(true) {
+            on_done(Ok(()));
+        }
+    });
+
+    print_operation.print();
+}
+
+/// Asks where to save the page, then writes it as a PDF.
+pub fn show_save_as_pdf_dialog(webview: &WebView, parent: &Window) {
+    let file_chooser = FileChooserNative::new(
+        Some("Save as PDF"),
+        Some(parent),
+        FileChooserAction::Save,
+        None,
+        None,
+    );
+
+    let title = webview
+        .title()
+        .map(|title| title.to_string())
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| "page".to_string());
+
+    file_chooser.set_current_name(&format!("{}.pdf", title.replace('/', "_")));
+    file_chooser.set_do_overwrite_confirmation(true);
+
+    // Runs until answered, which also keeps the dialog alive until then
+    if file_chooser.run() == ResponseType::Accept {
+        if let Some(path) = file_chooser.file().and_then(|file| file.path()) {
+            save_page_as_pdf(webview, &path.clone(), move |result| match result {
+                Ok(()) => println!("Saved PDF to {}", path.display()),
+                Err(err) => println!("Failed to save PDF: {}", err),
+            });
+        }
+    }
+
+    file_chooser.destroy();
+}
+
+/// Headless mode: loads `uri`, saves it to `output` as a PDF and returns the
+/// process exit code. Runs its own main loop.
+pub fn run_print_to_pdf(uri: &str, output: &Path) -> i32 {
+    let exit_code = Rc::new(Cell::new(1));
+    let load_failed = Rc::new(Cell::new(false));
+
+    let window = gtk::OffscreenWindow::new();
+    let webview = create_webview();
+    window.add(&webview);
+    window.show_all();
+
+    webview.connect_load_changed({
+        let exit_code = exit_code.clone();
+        let load_failed = load_failed.clone();
+        let output = output.to_path_buf();
+        move |webview, event| {
+            // A failed load still finishes, with nothing worth saving
+            if event != LoadEvent::Finished || load_failed.get() {
+                return;
+            }
+
+            let exit_code = exit_code.clone();
+            let output = output.clone();
+
+            save_page_as_pdf(webview, &output.clone(), move |result| {
+                match result {
+                    Ok(()) => {
+                        println!("Saved PDF to {}", output.display());
+                        exit_code.set(0);
+                    }
+                    Err(err) => println!("Failed to save PDF: {}", err),
+                }
+
+                gtk::main_quit();
+            });
+        }
+    });
+
+    webview.connect_load_failed(move |_, _, uri, error| {
+        println!("Failed to load {}: {}", uri, error);
+        load_failed.set(true);
+        gtk::main_quit();
+        false
+    });
+
+    webview.load_uri(uri);
+    gtk::main();
+
+    exit_code.get()
+}
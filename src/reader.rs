@@ -0,0 +1,412 @@
+use gtk::prelude::*;
+use gtk::{Entry, EntryIconPosition, Widget};
+use javascriptcore::ValueExt;
+use std::rc::Rc;
+use webkit2gtk::{
+    UserContentInjectedFrames, UserContentManagerExt, UserScript, UserScriptInjectionTime, WebView,
+    WebViewExt,
+};
+
+use crate::settings::Settings;
+use crate::tabs::{page_webview, tab_browser_window, tab_state, TabState};
+use crate::BrowserWindow;
+
+/// Script message used for article detection and reader preferences
+pub const READER_MESSAGE: &str = "abrwReader";
+
+const READER_ICON: &str = "text-x-generic-symbolic";
+
+/// Reports whether the page has enough paragraph text to be worth a reader view
+const READER_DETECT_SCRIPT: &str = r#"
+(function () {
+    let text = 0;
+    for (const p of document.querySelectorAll("p")) {
+        const length = p.textContent.trim().length;
+        if (length > 80) text += length;
+    }
+    const article = text > 1500 || (document.querySelector("article") !== null && text > 500);
+    window.webkit.messageHandlers.abrwReader.postMessage(JSON.stringify({ article }));
+})();
+"#;
+
+/// Readability-style extraction: paragraphs score their parent and, at half
+/// weight, their grandparent; the best scoring container is the article.
+const READER_EXTRACT_SCRIPT: &str = r#"
+(function () {
+    const scores = new Map();
+    const addScore = (element, score) => {
+        if (element && element !== document.documentElement) {
+            scores.set(element, (scores.get(element) || 0) + score);
+        }
+    };
+
+    for (const p of document.querySelectorAll("p, pre")) {
+        const text = p.textContent.trim();
+        if (text.length < 25) continue;
+
+        const score = 1 + text.split(",").length + Math.min(Math.floor(text.length / 100), 3);
+        addScore(p.parentElement, score);
+        addScore(p.parentElement && p.parentElement.parentElement, score / 2);
+    }
+
+    let best = document.body;
+    let bestScore = 0;
+    for (const [element, score] of scores) {
+        // Containers full of links are navigation, not content
+        const links = [...element.querySelectorAll("a")]
+            .reduce((total, a) => total + a.textContent.length, 0);
+        const linkDensity = links / Math.max(element.textContent.length, 1);
+        const adjusted = score * (1 - linkDensity);
+
+        if (adjusted > bestScore) {
+            best = element;
+            bestScore = adjusted;
+        }
+    }
+
+    const article = best.cloneNode(true);
+    const junk = /comment|share|social|footer|sidebar|related|promo|advert|^ad-|newsletter|subscribe/i;
+
+    // The article is shown with the site's address, so links and sources
+    // that could run code there are dropped
+    const urlAttributes = ["href", "src", "srcset", "action", "formaction", "poster", "data", "background", "xlink:href"];
+    const isSafeUrl = (element, name, value) => {
+        if (name === "srcset") {
+            return value.split(",").every((candidate) =>
+                isSafeUrl(element, "src", candidate.trim().split(/\s+/)[0] || ""));
+        }
+
+        let url;
+        try {
+            url = new URL(value, document.baseURI);
+        } catch (error) {
+            return false;
+        }
+
+        if (url.protocol === "http:" || url.protocol === "https:") return true;
+        if (url.protocol === "mailto:") return name === "href";
+        return url.protocol === "data:" && element.localName === "img" && /^data:image\/(?!svg)/i.test(value.trim());
+    };
+
+    article.querySelectorAll("script, style, noscript, iframe, frame, object, embed, applet, meta, link, base, template, form, nav, aside, button, input, select, textarea, svg, math")
+        .forEach((element) => element.remove());
+
+    for (const element of [...article.querySelectorAll("*")]) {
+        if (junk.test(element.className + " " + element.id)) {
+            element.remove();
+            continue;
+        }
+
+        for (const attribute of [...element.attributes]) {
+            const name = attribute.name.toLowerCase();
+
+            if (name.startsWith("on") || name === "style" ||
+                (urlAttributes.includes(name) && !isSafeUrl(element, name, attribute.value))) {
+                element.removeAttribute(attribute.name);
+            }
+        }
+    }
+
+    const byline = document.querySelector("[rel=author], .author, .byline, meta[name=author]");
+
+    return JSON.stringify({
+        title: document.title,
+        byline: byline ? (byline.content || byline.textContent).trim() : "",
+        content: article.innerHTML,
+    });
+})();
+"#;
+
+const READER_PAGE_SCRIPT: &str = r#"
+    const root = document.documentElement.style;
+    const prefs = JSON.parse(document.getElementById("prefs").textContent);
+
+    function apply(save = true) {
+        root.setProperty("--font-size", prefs.font_size + "px");
+        root.setProperty("--width", prefs.width + "px");
+        root.setProperty("--font", prefs.serif ? "Georgia, serif" : "\"Hack\", sans-serif");
+        if (save) window.webkit.messageHandlers.abrwReader.postMessage(JSON.stringify({ prefs }));
+    }
+
+    apply(false);
+
+    document.getElementById("smaller").onclick = () => { prefs.font_size = Math.max(prefs.font_size - 2, 10); apply(); };
+    document.getElementById("larger").onclick = () => { prefs.font_size = Math.min(prefs.font_size + 2, 40); apply(); };
+    document.getElementById("narrower").onclick = () => { prefs.width = Math.max(prefs.width - 100, 400); apply(); };
+    document.getElementById("wider").onclick = () => { prefs.width = Math.min(prefs.width + 100, 1600); apply(); };
+    document.getElementById("font").onclick = () => { prefs.serif = !prefs.serif; apply(); };
+"#;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReaderState {
+    /// The page looks like an article
+    pub available: bool,
+    /// The tab is showing the reader view
+    pub active: bool,
+    /// The reader view is being loaded and becomes active once committed
+    pub loading: bool,
+}
+
+pub fn add_reader_detection(webview: &WebView) {
+    if let Some(content_manager) = webview.user_content_manager() {
+        content_manager.register_script_message_handler(READER_MESSAGE);
+        content_manager.add_script(&UserScript::new(
+            READER_DETECT_SCRIPT,
+            UserContentInjectedFrames::TopFrame,
+            UserScriptInjectionTime::End,
+            &[],
+            &[],
+        ));
+    }
+}
+
+pub fn connect_reader_messages(webview: &WebView, state: Rc<TabState>) {
+    if let Some(content_manager) = webview.user_content_manager() {
+        let webview = webview.downgrade();
+
+        content_manager.connect_script_message_received(Some(READER_MESSAGE), move |_, result| {
+            let message: serde_json::Value = match result.js_value() {
+                Some(value) => {
+                    serde_json::from_str(&value.to_str()).unwrap_or(serde_json::Value::Null)
+                }
+                None => return,
+            };
+
+            if let Some(article) = message["article"].as_bool() {
+                let mut reader = state.reader.get();
+                reader.available = article;
+                state.reader.set(reader);
+
+                if let Some(page) = webview.upgrade().and_then(|webview| webview.parent()) {
+                    refresh_reader_icon(&page);
+                }
+            }
+
+            // Only the reader page has the controls, any other page could
+            // post to the handler
+            if !state.reader.get().active {
+                return;
+            }
+
+            if let Some(prefs) = message["prefs"].as_object() {
+                let mut settings = Settings::load();
+
+                if let Some(font_size) = prefs.get("font_size").and_then(|v| v.as_u64()) {
+                    settings.reader_font_size = font_size.clamp(10, 40) as u32;
+                }
+                if let Some(width) = prefs.get("width").and_then(|v| v.as_u64()) {
+                    settings.reader_width = width.clamp(400, 1600) as u32;
+                }
+                if let Some(serif) = prefs.get("serif").and_then(|v| v.as_bool()) {
+                    settings.reader_serif = serif;
+                }
+
+                settings.save();
+            }
+        });
+    }
+}
+
+/// Shows the reader icon in the address bar when `page` can be read in reader view.
+pub fn sync_reader_icon(search_bar: &Entry, page: &Widget) {
+    let reader = tab_state(page)
+        .map(|state| state.reader.get())
+        .unwrap_or_default();
+
+    if reader.available || reader.active {
+        search_bar.set_icon_from_icon_name(EntryIconPosition::Secondary, Some(READER_ICON));
+        search_bar.set_icon_tooltip_text(
+            EntryIconPosition::Secondary,
+            Some(if reader.active {
+                "Leave reader view"
+            } else {
+                "Reader view"
+            }),
+        );
+    } else {
+        search_bar.set_icon_from_icon_name(EntryIconPosition::Secondary, None);
+    }
+}
+
+fn refresh_reader_icon(page: &Widget) {
+    if let Some(browser_window) = tab_browser_window(page) {
+        let notebook = &browser_window.notebook;
+
+        if notebook.nth_page(notebook.current_page()).as_ref() == Some(page) {
+            sync_reader_icon(&browser_window.search_bar, page);
+        }
+    }
+}
+
+/// Called when a load commits: the tab is in reader view only if the reader
+/// page itself was what got committed.
+pub fn reset_reader_state(state: &TabState) {
+    let reader = state.reader.get();
+
+    state.reader.set(ReaderState {
+        available: false,
+        active: reader.loading,
+        loading: false,
+    });
+}
+
+pub fn toggle_reader_mode(browser_window: &BrowserWindow) {
+    let notebook = &browser_window.notebook;
+
+    let page = match notebook.nth_page(notebook.current_page()) {
+        Some(page) => page,
+        None => return,
+    };
+
+    let (state, webview) = match (tab_state(&page), page_webview(&page)) {
+        (Some(state), Some(webview)) => (state, webview),
+        _ => return,
+    };
+
+    let reader = state.reader.get();
+
+    if reader.active {
+        // The reader page keeps the article's address as its base URI
+        if let Some(uri) = webview.uri() {
+            webview.load_uri(&uri);
+        }
+        return;
+    }
+
+    if !reader.available {
+        return;
+    }
+
+    webview.clone().run_javascript(
+        READER_EXTRACT_SCRIPT,
+        gtk::gio::Cancellable::NONE,
+        move |result| {
+            let article: serde_json::Value = match result
+                .ok()
+                .and_then(|result| result.js_value())
+                .and_then(|value| serde_json::from_str(&value.to_str()).ok())
+            {
+                Some(article) => article,
+                None => {
+                    println!("Failed to extract article");
+                    return;
+                }
+            };
+
+            let mut reader = state.reader.get();
+            reader.loading = true;
+            state.reader.set(reader);
+
+            webview.load_html(&reader_html(&article), webview.uri().as_deref());
+        },
+    );
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn reader_html(article: &serde_json::Value) -> String {
+    let settings = Settings::load();
+
+    let prefs = serde_json::json!({
+        "font_size": if settings.reader_font_size == 0 { 18 } else { settings.reader_font_size },
+        "width": if settings.reader_width == 0 { 700 } else { settings.reader_width },
+        "serif": settings.reader_serif,
+    });
+
+    let title = escape_html(article["title"].as_str().unwrap_or_default());
+    let byline = escape_html(article["byline"].as_str().unwrap_or_default());
+    let content = article["content"].as_str().unwrap_or_default();
+
+    format!(
+        r##"
+    <!DOCTYPE html>
+    <html lang="en">
+    <head>
+        <meta charset="UTF-8">
+        <meta name="viewport" content="width=device-width, initial-scale=1.0">
+        <title>{title}</title>
+        <style>
+            :root {{
+                --font-size: 18px;
+                --width: 700px;
+                --font: "Hack", sans-serif;
+            }}
+            body, html {{
+                margin: 0;
+                background-color: #202020;
+                color: #f1f1f1;
+            }}
+            .toolbar {{
+                position: fixed;
+                top: 10px;
+                right: 10px;
+            }}
+            .toolbar button {{
+                background: #2a2a2a;
+                color: #f1f1f1;
+                border: none;
+                border-radius: 7px;
+                padding: 5px 10px;
+                cursor: pointer;
+            }}
+            .toolbar button:hover {{
+                background: #333;
+            }}
+            main {{
+                max-width: var(--width);
+                margin: 0 auto;
+                padding: 40px 20px;
+                font-family: var(--font);
+                font-size: var(--font-size);
+                line-height: 1.6;
+            }}
+            h1 {{
+                line-height: 1.2;
+            }}
+            .darkened {{
+                color: #888;
+            }}
+            a {{
+                color: #88c0d0;
+            }}
+            img, video, figure {{
+                max-width: 100%;
+                height: auto;
+            }}
+            pre {{
+                background: #2a2a2a;
+                padding: 10px;
+                overflow-x: auto;
+            }}
+        </style>
+    </head>
+    <body>
+        <div class="toolbar">
+            <button id="smaller">A-</button>
+            <button id="larger">A+</button>
+            <button id="narrower">&lt;&gt;</button>
+            <button id="wider">&lt; &gt;</button>
+            <button id="font">Serif / Sans</button>
+        </div>
+        <main>
+            <h1>{title}</h1>
+            <p class="darkened">{byline}</p>
+            {content}
+        </main>
+        <script type="application/json" id="prefs">{prefs}</script>
+        <script>{script}</script>
+    </body>
+    </html>
+    "##,
+        title = title,
+        byline = byline,
+        content = content,
+        prefs = prefs,
+        script = READER_PAGE_SCRIPT,
+    )
+}
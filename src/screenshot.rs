@@ -0,0 +1,221 @@
+use gtk::gdk::keys::constants;
+use gtk::gdk_pixbuf::Pixbuf;
+use gtk::glib::Propagation;
+use gtk::prelude::*;
+use gtk::{DrawingArea, Window, WindowType};
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use webkit2gtk::{SnapshotOptions, SnapshotRegion, WebView, WebViewExt};
+
+use crate::settings::Settings;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScreenshotArea {
+    Visible,
+    FullPage,
+    /// Drag a rectangle over the visible area
+    Region,
+}
+
+/// Takes a screenshot of `webview` and saves it, or copies it to the
+/// clipboard if that is enabled in settings.
+pub fn take_screenshot(webview: &WebView, parent: &Window, area: ScreenshotArea) {
+    let region = match area {
+        ScreenshotArea::FullPage => SnapshotRegion::FullDocument,
+        ScreenshotArea::Visible | ScreenshotArea::Region => SnapshotRegion::Visible,
+    };
+
+    let parent = parent.clone();
+
+    webview.snapshot(
+        region,
+        SnapshotOptions::NONE,
+        gtk::gio::Cancellable::NONE,
+        move |result| {
+            let pixbuf = match result
+                .ok()
+                .and_then(|surface| gtk::cairo::ImageSurface::try_from(surface).ok())
+                .and_then(|surface| {
+                    gtk::gdk::pixbuf_get_from_surface(
+                        &surface,
+                        0,
+                        0,
+                        surface.width(),
+                        surface.height(),
+                    )
+                }) {
+                Some(pixbuf) => pixbuf,
+                None => {
+                    println!("Failed to take screenshot");
+                    return;
+                }
+            };
+
+            match area {
+                ScreenshotArea::Region => show_region_selector(&parent, pixbuf),
+                _ => deliver_screenshot(&pixbuf),
+            }
+        },
+    );
+}
+
+/// Folder screenshots are saved to, the Pictures folder unless set in settings.
+fn screenshot_dir() -> PathBuf {
+    let settings = Settings::load();
+
+    if !settings.screenshot_dir.is_empty() {
+        return PathBuf::from(settings.screenshot_dir);
+    }
+
+    dirs::picture_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+fn deliver_screenshot(pixbuf: &Pixbuf) {
+    if Settings::load().screenshot_to_clipboard {
+        gtk::Clipboard::get(&gtk::gdk::SELECTION_CLIPBOARD).set_image(pixbuf);
+        println!("Screenshot copied to clipboard");
+        return;
+    }
+
+    let dir = screenshot_dir();
+
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        println!("Failed to create {}: {}", dir.display(), err);
+        return;
+    }
+
+    let timestamp = gtk::glib::DateTime::now_local()
+        .and_then(|now| now.format("%Y-%m-%d-%H%M%S"))
+        .map(|timestamp| timestamp.to_string())
+        .unwrap_or_default();
+    let path = dir.join(format!("abrw-screenshot-{}.png", timestamp));
+
+    match pixbuf.savev(&path, "png", &[]) {
+        Ok(()) => println!("Screenshot saved to {}", path.display()),
+        Err(err) => println!("Failed to save screenshot: {}", err),
+    }
+}
+
+/// Shows the snapshot and lets the user drag out the part to keep.
+fn show_region_selector(parent: &Window, pixbuf: Pixbuf) {
+    let window = Window::new(WindowType::Toplevel);
+    window.set_title("Select region");
+    window.set_transient_for(Some(parent));
+    window.set_modal(true);
+    window.set_decorated(false);
+    window.set_default_size(pixbuf.width(), pixbuf.height());
+    window.set_position(gtk::WindowPosition::CenterOnParent);
+
+    let drawing_area = DrawingArea::new();
+    drawing_area.add_events(
+        gtk::gdk::EventMask::BUTTON_PRESS_MASK
+            | gtk::gdk::EventMask::BUTTON_RELEASE_MASK
+            | gtk::gdk::EventMask::BUTTON_MOTION_MASK,
+    );
+    window.add(&drawing_area);
+
+    // Corners of the rectangle being dragged, in widget coordinates
+    let start = Rc::new(Cell::new(None::<(f64, f64)>));
+    let end = Rc::new(Cell::new((0.0, 0.0)));
+
+    drawing_area.connect_draw({
+        let pixbuf = pixbuf.clone();
+        let start = start.clone();
+        let end = end.clone();
+
+        move |_, cr| {
+            cr.set_source_pixbuf(&pixbuf, 0.0, 0.0);
+            cr.paint().ok();
+
+            // Dim everything, then paint the selection back at full brightness
+            cr.set_source_rgba(0.0, 0.0, 0.0, 0.4);
+            cr.paint().ok();
+
+            if let Some((x, y, width, height)) = selection_rect(start.get(), end.get()) {
+                cr.rectangle(x, y, width, height);
+                cr.clip();
+                cr.set_source_pixbuf(&pixbuf, 0.0, 0.0);
+                cr.paint().ok();
+                cr.reset_clip();
+
+                cr.rectangle(x, y, width, height);
+                cr.set_source_rgb(0.92, 0.8, 0.55);
+                cr.set_line_width(1.0);
+                cr.stroke().ok();
+            }
+
+            Propagation::Stop
+        }
+    });
+
+    drawing_area.connect_button_press_event({
+        let start = start.clone();
+        let end = end.clone();
+
+        move |_, event| {
+            start.set(Some(event.position()));
+            end.set(event.position());
+            Propagation::Stop
+        }
+    });
+
+    drawing_area.connect_motion_notify_event({
+        let end = end.clone();
+
+        move |drawing_area, event| {
+            end.set(event.position());
+            drawing_area.queue_draw();
+            Propagation::Stop
+        }
+    });
+
+    drawing_area.connect_button_release_event({
+        let window = window.clone();
+
+        move |_, event| {
+            end.set(event.position());
+
+            if let Some((x, y, width, height)) = selection_rect(start.get(), end.get()) {
+                // Keep the rectangle inside the snapshot
+                let x = (x as i32).clamp(0, pixbuf.width() - 1);
+                let y = (y as i32).clamp(0, pixbuf.height() - 1);
+                let width = (width as i32).min(pixbuf.width() - x);
+                let height = (height as i32).min(pixbuf.height() - y);
+
+                if width > 1 && height > 1 {
+                    deliver_screenshot(&pixbuf.new_subpixbuf(x, y, width, height));
+                    window.close();
+                }
+            }
+
+            start.set(None);
+            Propagation::Stop
+        }
+    });
+
+    window.connect_key_press_event(|window, key| {
+        if key.keyval() == constants::Escape {
+            window.close();
+            return Propagation::Stop;
+        }
+
+        Propagation::Proceed
+    });
+
+    window.show_all();
+}
+
+fn selection_rect(start: Option<(f64, f64)>, end: (f64, f64)) -> Option<(f64, f64, f64, f64)> {
+    let (start_x, start_y) = start?;
+    let (end_x, end_y) = end;
+
+    Some((
+        start_x.min(end_x),
+        start_y.min(end_y),
+        (end_x - start_x).abs(),
+        (end_y - start_y).abs(),
+    ))
+}
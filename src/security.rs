@@ -0,0 +1,293 @@
+use gtk::gio::TlsCertificate;
+use gtk::prelude::*;
+use gtk::{Box, Entry, EntryIconPosition, Label, Orientation, Popover, Widget};
+use std::rc::Rc;
+use url::Url;
+use webkit2gtk::{LoadEvent, WebView, WebViewExt};
+
+use crate::permissions::{page_origin, SitePermissions};
+use crate::styles::apply_css_style;
+use crate::tabs::{page_webview, tab_browser_window, tab_state, TabState};
+use crate::tls::{certificate_details, certificate_error_descriptions, TlsExceptions};
+use crate::webview::{get_webview_setting, WebviewSetting};
+use crate::zoom::site_zoom_level;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SecurityState {
+    /// abrw://, file:// and other pages that never touch the network
+    Local,
+    /// HTTPS page that is still loading or failed to load, so nothing came
+    /// over the connection yet
+    Pending,
+    Secure,
+    /// HTTPS page that loaded some of its content over HTTP
+    MixedContent,
+    /// The certificate has errors, either on the error page or through an exception
+    CertificateError,
+    Insecure,
+}
+
+impl SecurityState {
+    fn icon_name(&self) -> Option<&'static str> {
+        match self {
+            SecurityState::Local | SecurityState::Pending => None,
+            SecurityState::Secure => Some("channel-secure-symbolic"),
+            SecurityState::MixedContent => Some("dialog-warning-symbolic"),
+            SecurityState::CertificateError => Some("dialog-error-symbolic"),
+            SecurityState::Insecure => Some("channel-insecure-symbolic"),
+        }
+    }
+
+    fn summary(&self) -> &'static str {
+        match self {
+            SecurityState::Local => "This is a local page",
+            SecurityState::Pending => "Not connected yet",
+            SecurityState::Secure => "Connection is secure",
+            SecurityState::MixedContent => "Connection is partially secure",
+            SecurityState::CertificateError => "Certificate is not trusted",
+            SecurityState::Insecure => "Connection is not secure",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            SecurityState::Local => "It was not loaded from the network.",
+            SecurityState::Pending => "Nothing was loaded from this site yet.",
+            SecurityState::Secure => "Information you send to this site is encrypted.",
+            SecurityState::MixedContent => {
+                "Parts of this page were loaded without encryption and could be changed by others."
+            }
+            SecurityState::CertificateError => {
+                "The site's identity could not be verified. Don't enter passwords or payment details."
+            }
+            SecurityState::Insecure => {
+                "Information you send to this site can be read and changed by others."
+            }
+        }
+    }
+}
+
+/// Works out how the page in `webview` was loaded.
+pub fn page_security(webview: &WebView, state: &TabState) -> SecurityState {
+    let uri = match webview.uri().and_then(|uri| Url::parse(&uri).ok()) {
+        Some(uri) => uri,
+        None => return SecurityState::Local,
+    };
+
+    match uri.scheme() {
+        "https" => {}
+        "http" => return SecurityState::Insecure,
+        _ => return SecurityState::Local,
+    }
+
+    let showing_tls_error = state
+        .tls_error
+        .borrow()
+        .as_ref()
+        .is_some_and(|pending| pending.uri == uri.as_str());
+
+    if showing_tls_error {
+        return SecurityState::CertificateError;
+    }
+
+    match webview.tls_info() {
+        Some((_, errors)) if !errors.is_empty() => SecurityState::CertificateError,
+        Some(_) if state.mixed_content.get() => SecurityState::MixedContent,
+        Some(_) => SecurityState::Secure,
+        // The error page and pages still loading have no certificate yet
+        None => SecurityState::Pending,
+    }
+}
+
+/// Shows the security state of `page` in the address bar.
+pub fn sync_security_icon(search_bar: &Entry, page: &Widget) {
+    let security = match (page_webview(page), tab_state(page)) {
+        (Some(webview), Some(state)) => page_security(&webview, &state),
+        _ => SecurityState::Local,
+    };
+
+    search_bar.set_icon_from_icon_name(EntryIconPosition::Primary, security.icon_name());
+    search_bar.set_icon_tooltip_text(EntryIconPosition::Primary, Some(security.summary()));
+}
+
+fn refresh_security_icon(page: &Widget) {
+    if let Some(browser_window) = tab_browser_window(page) {
+        let notebook = &browser_window.notebook;
+
+        if notebook.nth_page(notebook.current_page()).as_ref() == Some(page) {
+            sync_security_icon(&browser_window.search_bar, page);
+        }
+    }
+}
+
+/// Keeps the security icon up to date while `webview` loads.
+pub fn connect_security_indicator(webview: &WebView, state: Rc<TabState>) {
+    webview.connect_load_changed({
+        let state = state.clone();
+        move |webview, event| {
+            if event == LoadEvent::Committed {
+                state.mixed_content.set(false);
+            }
+
+            if let Some(page) = webview.parent() {
+                refresh_security_icon(&page);
+            }
+        }
+    });
+
+    webview.connect_insecure_content_detected(move |webview, _| {
+        state.mixed_content.set(true);
+
+        if let Some(page) = webview.parent() {
+            refresh_security_icon(&page);
+        }
+    });
+}
+
+fn popover_label(text: &str, css: &str) -> Label {
+    let label = Label::new(Some(text));
+    label.set_halign(gtk::Align::Start);
+    label.set_xalign(0.0);
+    label.set_line_wrap(true);
+    label.set_max_width_chars(50);
+    label.set_selectable(true);
+    apply_css_style(&[label.upcast_ref()], css);
+    label
+}
+
+fn add_heading(popup_box: &Box, text: &str) {
+    popup_box.pack_start(
+        &popover_label(text, "label { color: #f1f1f1; font-weight: bold; }"),
+        false,
+        false,
+        5,
+    );
+}
+
+fn add_row(popup_box: &Box, name: &str, value: &str) {
+    popup_box.pack_start(
+        &popover_label(&format!("{}: {}", name, value), "label { color: #888; }"),
+        false,
+        false,
+        2,
+    );
+}
+
+/// The certificate and the ones that signed it, as far as GIO knows them.
+fn certificate_chain(certificate: &TlsCertificate) -> Vec<TlsCertificate> {
+    let mut chain = vec![certificate.clone()];
+
+    while let Some(issuer) = chain.last().and_then(|certificate| certificate.issuer()) {
+        // Self-signed roots are their own issuer
+        if chain.iter().any(|certificate| certificate.is_same(&issuer)) {
+            break;
+        }
+        chain.push(issuer);
+    }
+
+    chain
+}
+
+/// Popover shown from the security icon, with the connection, the
+/// certificate chain and what is set for the current site.
+pub fn show_security_popover(search_bar: &Entry, page: &Widget) {
+    let (webview, state) = match (page_webview(page), tab_state(page)) {
+        (Some(webview), Some(state)) => (webview, state),
+        _ => return,
+    };
+
+    let security = page_security(&webview, &state);
+    let host = webview
+        .uri()
+        .and_then(|uri| Url::parse(&uri).ok())
+        .and_then(|url| url.host_str().map(|host| host.to_string()));
+
+    let popup = Popover::new(Some(search_bar));
+    popup.set_pointing_to(&search_bar.icon_area(EntryIconPosition::Primary));
+
+    apply_css_style(
+        &[popup.upcast_ref()],
+        "popover { background: #2a2a2a; box-shadow: none; padding: 7px; }",
+    );
+
+    let popup_box = Box::new(Orientation::Vertical, 0);
+
+    add_heading(&popup_box, security.summary());
+    popup_box.pack_start(
+        &popover_label(security.description(), "label { color: #888; }"),
+        false,
+        false,
+        2,
+    );
+
+    if let Some((certificate, errors)) = webview.tls_info() {
+        for description in certificate_error_descriptions(errors) {
+            popup_box.pack_start(
+                &popover_label(description, "label { color: #e5a50a; }"),
+                false,
+                false,
+                2,
+            );
+        }
+
+        for (index, certificate) in certificate_chain(&certificate).iter().enumerate() {
+            add_heading(
+                &popup_box,
+                if index == 0 {
+                    "Certificate"
+                } else {
+                    "Issued by"
+                },
+            );
+
+            for (name, value) in certificate_details(certificate) {
+                add_row(&popup_box, name, &value);
+            }
+        }
+    }
+
+    if let Some(host) = host {
+        add_heading(&popup_box, &host);
+
+        if let Some(javascript) = get_webview_setting(&webview, WebviewSetting::Javascript) {
+            add_row(
+                &popup_box,
+                "JavaScript",
+                if javascript { "Allowed" } else { "Blocked" },
+            );
+        }
+
+        if let Some(uri) = webview.uri() {
+            add_row(
+                &popup_box,
+                "Zoom",
+                &format!("{}%", (site_zoom_level(&uri) * 100.0).round()),
+            );
+        }
+
+        if TlsExceptions::load().hosts.contains_key(&host) {
+            add_row(&popup_box, "Certificate", "Trusted by exception");
+        }
+
+        let origin = webview.uri().as_deref().and_then(page_origin);
+
+        if let Some(decisions) =
+            origin.and_then(|origin| SitePermissions::load().origins.remove(&origin))
+        {
+            let mut decisions: Vec<_> = decisions.into_iter().collect();
+            decisions.sort();
+
+            for (kind, allowed) in decisions {
+                add_row(
+                    &popup_box,
+                    kind.title(),
+                    if allowed { "Allowed" } else { "Blocked" },
+                );
+            }
+        }
+    }
+
+    popup.add(&popup_box);
+    popup_box.show_all();
+    popup.popup();
+}
@@ -0,0 +1,160 @@
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+
+use crate::groups::{
+    is_group_collapsed, notebook_groups, set_group_collapsed, set_tab_group, tab_group, TabGroup,
+};
+use crate::settings::Settings;
+use crate::storage::{load_json, save_json};
+use crate::tabs::{add_tab, is_tab_pinned, set_tab_pinned, tab_uri};
+use crate::{browser_windows, create_empty_window, create_window};
+
+thread_local! {
+    // Set while the session is restored, so the half restored windows don't
+    // overwrite the saved session
+    static RESTORING: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SessionTab {
+    pub uri: Option<String>,
+    pub pinned: bool,
+    #[serde(default)]
+    pub group: Option<TabGroup>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SessionGroup {
+    pub name: String,
+    pub color: String,
+    pub collapsed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SessionWindow {
+    pub tabs: Vec<SessionTab>,
+    #[serde(default)]
+    pub groups: Vec<SessionGroup>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Session {
+    pub windows: Vec<SessionWindow>,
+}
+
+impl Session {
+    pub fn save(&self) {
+        save_json("session.json", self);
+    }
+
+    pub fn load() -> Session {
+        load_json("session.json")
+    }
+
+    /// Snapshot of every open window and its tabs, in notebook order.
+    pub fn capture() -> Session {
+        let windows = browser_windows()
+            .iter()
+            .map(|browser_window| SessionWindow {
+                tabs: browser_window
+                    .notebook
+                    .children()
+                    .iter()
+                    .map(|page| SessionTab {
+                        uri: tab_uri(page),
+                        pinned: is_tab_pinned(page),
+                        group: tab_group(page),
+                    })
+                    .collect(),
+                groups: notebook_groups(&browser_window.notebook)
+                    .into_iter()
+                    .map(|group| SessionGroup {
+                        collapsed: is_group_collapsed(&browser_window.notebook, &group),
+                        name: group.name,
+                        color: group.color,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Session { windows }
+    }
+}
+
+pub fn save_session() {
+    if RESTORING.with(|restoring| restoring.get()) {
+        return;
+    }
+
+    Session::capture().save();
+}
+
+/// Reopens the previous session if enabled in settings. Pinned tabs are
+/// always reopened, gathered into a single window when restore is off.
+pub fn restore_session() {
+    let session = Session::load();
+
+    let windows: Vec<SessionWindow> = if Settings::load().restore_session {
+        session.windows
+    } else {
+        vec![SessionWindow {
+            tabs: session
+                .windows
+                .into_iter()
+                .flat_map(|window| window.tabs)
+                .filter(|tab| tab.pinned)
+                .map(|tab| SessionTab { group: None, ..tab })
+                .collect(),
+            groups: Vec::new(),
+        }]
+    };
+
+    let windows: Vec<SessionWindow> = windows
+        .into_iter()
+        .filter(|window| !window.tabs.is_empty())
+        .collect();
+
+    if windows.is_empty() {
+        create_window(None);
+        return;
+    }
+
+    RESTORING.with(|restoring| restoring.set(true));
+
+    for window in windows {
+        let browser_window = create_empty_window();
+
+        for tab in window.tabs {
+            let page = add_tab(
+                &browser_window.notebook,
+                &browser_window.search_bar,
+                tab.uri.as_deref(),
+            );
+
+            if tab.pinned {
+                set_tab_pinned(&page, true);
+            }
+
+            // Saved whole, as groups can share a name and differ in color
+            if tab.group.is_some() {
+                set_tab_group(&page, tab.group);
+            }
+        }
+
+        for group in window.groups.iter().filter(|group| group.collapsed) {
+            set_group_collapsed(
+                &browser_window.notebook,
+                &TabGroup {
+                    name: group.name.clone(),
+                    color: group.color.clone(),
+                },
+                true,
+            );
+        }
+    }
+
+    RESTORING.with(|restoring| restoring.set(false));
+
+    save_session();
+}
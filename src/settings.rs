@@ -1,15 +1,23 @@
 use gtk::{prelude::*, STYLE_PROVIDER_PRIORITY_APPLICATION};
-use gtk::{Box, Label, Orientation, Switch, Window, WindowType};
+use gtk::{Box, Entry, Label, Orientation, SpinButton, Switch, Window, WindowType};
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::fs;
 use std::rc::Rc;
 use webkit2gtk::WebView;
 
+use crate::devtools::set_devtools_disabled;
+use crate::https::create_https_exceptions_section;
+use crate::permissions::create_site_permissions_section;
+use crate::sitesettings::create_site_settings_section;
+use crate::storage::{load_json, save_json};
 use crate::styles::apply_css_style;
+use crate::tls::create_tls_exceptions_section;
+use crate::vim::set_vim_enabled;
 use crate::webview::toggle_content_filter;
+use crate::zoom::set_zoom_text_only;
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
 pub struct Settings {
     pub private_browsing: bool,
     pub enable_javascript: bool,
@@ -30,19 +38,39 @@ pub struct Settings {
     pub javascript_can_open_windows_automatically: bool,
     pub javascript_can_access_clipboard: bool,
     pub enable_site_specific_quirks: bool,
+    pub restore_session: bool,
+    pub tab_suspend_minutes: u32,
+    pub vertical_tabs: bool,
+    pub vim_mode: bool,
+    pub default_zoom: u32,
+    pub zoom_text_only: bool,
+    pub screenshot_dir: String,
+    pub screenshot_to_clipboard: bool,
+    pub reader_font_size: u32,
+    pub reader_width: u32,
+    pub reader_serif: bool,
+    pub disable_developer_extras: bool,
+    pub devtools_detached: bool,
+    pub newtab_url: String,
+    pub newtab_background: String,
+    pub https_only: bool,
 }
 
 impl Settings {
     pub fn save(&self) {
-        let json_data = serde_json::to_string(self).expect("Failed to serialize settings.");
-        fs::write("settings.json", json_data).expect("Failed to write settings to file.");
+        save_json("settings.json", self);
     }
 
     pub fn load() -> Settings {
-        if let Ok(data) = fs::read_to_string("settings.json") {
-            serde_json::from_str(&data).unwrap_or_default()
+        load_json("settings.json")
+    }
+
+    /// Default page zoom in percent. Unset means 100%.
+    pub fn default_zoom_percent(&self) -> u32 {
+        if self.default_zoom == 0 {
+            100
         } else {
-            Settings::default()
+            self.default_zoom
         }
     }
 }
@@ -105,6 +133,80 @@ pub fn show_settings_window() {
         &settings,
     );
 
+    create_setting(
+        "Restore previous session",
+        |s| s.restore_session,
+        |s, v| s.restore_session = v,
+        &general_box,
+        &settings,
+    );
+
+    create_setting(
+        "Vim-style keyboard navigation",
+        |s| s.vim_mode,
+        |s, v| s.vim_mode = v,
+        &general_box,
+        &settings,
+    );
+
+    create_number_setting(
+        "Suspend idle tabs after minutes (0 = never)",
+        |s| s.tab_suspend_minutes,
+        |s, v| s.tab_suspend_minutes = v,
+        (0, 1440),
+        &general_box,
+        &settings,
+    );
+
+    create_number_setting(
+        "Default zoom (%)",
+        |s| s.default_zoom_percent(),
+        |s, v| s.default_zoom = v,
+        (30, 500),
+        &general_box,
+        &settings,
+    );
+
+    create_setting(
+        "Zoom text only",
+        |s| s.zoom_text_only,
+        |s, v| s.zoom_text_only = v,
+        &general_box,
+        &settings,
+    );
+
+    create_text_setting(
+        "Screenshot folder (empty = Pictures)",
+        |s| s.screenshot_dir.clone(),
+        |s, v| s.screenshot_dir = v,
+        &general_box,
+        &settings,
+    );
+
+    create_setting(
+        "Copy screenshots to clipboard instead of saving",
+        |s| s.screenshot_to_clipboard,
+        |s, v| s.screenshot_to_clipboard = v,
+        &general_box,
+        &settings,
+    );
+
+    create_text_setting(
+        "New tab page URL or HTML file (empty = speed dial)",
+        |s| s.newtab_url.clone(),
+        |s, v| s.newtab_url = v,
+        &general_box,
+        &settings,
+    );
+
+    create_text_setting(
+        "New tab background image",
+        |s| s.newtab_background.clone(),
+        |s, v| s.newtab_background = v,
+        &general_box,
+        &settings,
+    );
+
     create_setting(
         "Private browsing",
         |s| s.private_browsing,
@@ -121,6 +223,14 @@ pub fn show_settings_window() {
         &settings,
     );
 
+    create_setting(
+        "HTTPS-only mode",
+        |s| s.https_only,
+        |s, v| s.https_only = v,
+        &privacy_box,
+        &settings,
+    );
+
     create_setting(
         "Enable WebGL",
         |s| s.enable_webgl,
@@ -201,6 +311,14 @@ pub fn show_settings_window() {
         &settings,
     );
 
+    create_setting(
+        "Disable developer tools",
+        |s| s.disable_developer_extras,
+        |s, v| s.disable_developer_extras = v,
+        &advanced_box,
+        &settings,
+    );
+
     create_setting(
         "Enable Site Specific Quirks",
         |s| s.enable_site_specific_quirks,
@@ -209,6 +327,11 @@ pub fn show_settings_window() {
         &settings,
     );
 
+    create_tls_exceptions_section(&privacy_box);
+    create_https_exceptions_section(&privacy_box);
+    create_site_permissions_section(&privacy_box);
+    create_site_settings_section(&privacy_box);
+
     settings_stack.add_named(&general_box, "General");
     settings_stack.add_named(&privacy_box, "Privacy");
     settings_stack.add_named(&advanced_box, "Advanced");
@@ -260,11 +383,141 @@ fn create_setting(
 
     switch.connect_active_notify(move |switch| {
         let mut settings = settings_clone.borrow_mut();
+        let previous = settings.clone();
         set_value(&mut settings, switch.is_active());
+        apply_setting_changes(&previous, &settings);
         settings.save(); // Save settings to file
     });
 }
 
+/// Applies the settings open tabs follow right away, when they changed from
+/// `previous`. The settings window and abrw://settings both go through here.
+pub fn apply_setting_changes(previous: &Settings, settings: &Settings) {
+    if settings.vim_mode != previous.vim_mode {
+        set_vim_enabled(settings.vim_mode);
+    }
+
+    if settings.zoom_text_only != previous.zoom_text_only {
+        set_zoom_text_only(settings.zoom_text_only);
+    }
+
+    if settings.disable_developer_extras != previous.disable_developer_extras {
+        set_devtools_disabled(settings.disable_developer_extras);
+    }
+}
+
+fn create_number_setting(
+    label: &str,
+    get_value: fn(&Settings) -> u32,
+    set_value: fn(&mut Settings, u32),
+    (min, max): (u32, u32),
+    parent_box: &Box,
+    settings: &Rc<RefCell<Settings>>,
+) {
+    let hbox = Box::new(Orientation::Horizontal, 0);
+    let setting_label = Label::new(Some(label));
+    let spin_button = SpinButton::with_range(min as f64, max as f64, 1.0);
+    let settings_clone = Rc::clone(settings);
+    spin_button.set_value(get_value(&settings_clone.borrow()) as f64);
+
+    setting_label.set_halign(gtk::Align::Start);
+
+    hbox.pack_start(&setting_label, true, true, 5);
+    hbox.pack_end(&spin_button, false, false, 5);
+    parent_box.pack_start(&hbox, false, false, 5);
+
+    spin_button.connect_value_changed(move |spin_button| {
+        let mut settings = settings_clone.borrow_mut();
+        set_value(&mut settings, spin_button.value_as_int() as u32);
+        settings.save();
+    });
+}
+
+fn create_text_setting(
+    label: &str,
+    get_value: fn(&Settings) -> String,
+    set_value: fn(&mut Settings, String),
+    parent_box: &Box,
+    settings: &Rc<RefCell<Settings>>,
+) {
+    let hbox = Box::new(Orientation::Horizontal, 0);
+    let setting_label = Label::new(Some(label));
+    let entry = Entry::new();
+    let settings_clone = Rc::clone(settings);
+    entry.set_text(&get_value(&settings_clone.borrow()));
+
+    setting_label.set_halign(gtk::Align::Start);
+
+    hbox.pack_start(&setting_label, true, true, 5);
+    hbox.pack_end(&entry, false, false, 5);
+    parent_box.pack_start(&hbox, false, false, 5);
+
+    entry.connect_changed(move |entry| {
+        let mut settings = settings_clone.borrow_mut();
+        set_value(&mut settings, entry.text().to_string());
+        settings.save();
+    });
+}
+
+/// A row of a list in the settings window. `detail` is shown darkened next
+/// to the label, `key` is what removing the row is about.
+pub struct SettingsListRow<T> {
+    pub label: String,
+    pub detail: Option<String>,
+    pub key: T,
+}
+
+/// Adds a titled list to `parent_box`, each row with a button that calls
+/// `remove` with its key and takes the row out of the list.
+pub fn create_settings_list<T: 'static>(
+    parent_box: &Box,
+    title: &str,
+    empty_text: &str,
+    (button_label, button_tooltip): (&str, Option<&str>),
+    rows: Vec<SettingsListRow<T>>,
+    remove: impl Fn(&T) + 'static,
+) {
+    let title = Label::new(Some(title));
+    title.set_halign(gtk::Align::Start);
+    parent_box.pack_start(&title, false, false, 5);
+
+    if rows.is_empty() {
+        let empty = Label::new(Some(empty_text));
+        empty.set_halign(gtk::Align::Start);
+        apply_css_style(&[empty.upcast_ref()], "label { color: #888; }");
+        parent_box.pack_start(&empty, false, false, 5);
+        return;
+    }
+
+    let remove = Rc::new(remove);
+
+    for row in rows {
+        let hbox = Box::new(Orientation::Horizontal, 0);
+        let row_label = Label::new(Some(&row.label));
+        let remove_button = gtk::Button::with_label(button_label);
+
+        row_label.set_halign(gtk::Align::Start);
+        remove_button.set_tooltip_text(button_tooltip);
+
+        hbox.pack_start(&row_label, true, true, 5);
+
+        if let Some(detail) = &row.detail {
+            let detail_label = Label::new(Some(detail));
+            apply_css_style(&[detail_label.upcast_ref()], "label { color: #888; }");
+            hbox.pack_start(&detail_label, false, false, 5);
+        }
+
+        hbox.pack_end(&remove_button, false, false, 5);
+        parent_box.pack_start(&hbox, false, false, 5);
+
+        let remove = remove.clone();
+        remove_button.connect_clicked(move |_| {
+            remove(&row.key);
+            unsafe { hbox.destroy() };
+        });
+    }
+}
+
 pub fn toggle_adblock(adblock_enabled: Rc<RefCell<bool>>, webview: &WebView) {
     let current_value = *adblock_enabled.borrow();
     *adblock_enabled.borrow_mut() = !current_value;
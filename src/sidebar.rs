@@ -0,0 +1,389 @@
+use gtk::gdk_pixbuf::Pixbuf;
+use gtk::glib::Propagation;
+use gtk::prelude::*;
+use gtk::{
+    Box, Button, CellRendererPixbuf, CellRendererText, Notebook, ScrolledWindow, TreeIter,
+    TreeModel, TreeStore, TreeView, TreeViewColumn, Widget,
+};
+use std::cell::Cell;
+
+use crate::groups::{
+    is_group_collapsed, set_group_collapsed, show_group_menu, tab_group, TabGroup,
+};
+use crate::settings::Settings;
+use crate::styles::apply_css_style;
+use crate::tabs::{add_tab, close_tab, show_tab_menu, tab_opener, tab_state, tab_title, TabState};
+use crate::{browser_windows, create_button_with_icon, find_browser_window, BrowserWindow};
+
+const SIDEBAR_WIDTH: i32 = 250;
+
+const COLUMN_PIXBUF: u32 = 0;
+const COLUMN_ICON_NAME: u32 = 1;
+const COLUMN_TITLE: u32 = 2;
+const COLUMN_FOREGROUND: u32 = 3;
+const COLUMN_PAGE: u32 = 4;
+/// Name of the group a group row stands for, its color is the foreground
+const COLUMN_GROUP: u32 = 5;
+
+thread_local! {
+    // Set while the tree is rebuilt, so selecting the current row doesn't
+    // feed back into the notebook
+    static REBUILDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Vertical tab list shown in place of the notebook header.
+/// Tabs opened from a link are nested under the tab they were opened from.
+pub fn create_tab_sidebar(notebook: &Notebook) -> (Box, TreeView) {
+    let sidebar = Box::new(gtk::Orientation::Vertical, 0);
+    let header = Box::new(gtk::Orientation::Horizontal, 0);
+
+    let collapse_button = Button::with_label("«");
+    let new_tab_button = create_button_with_icon("plus");
+
+    header.pack_start(&collapse_button, false, false, 5);
+    header.pack_end(&new_tab_button, false, false, 5);
+
+    let store = TreeStore::new(&[
+        Pixbuf::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        Widget::static_type(),
+        String::static_type(),
+    ]);
+
+    let tree_view = TreeView::with_model(&store);
+    tree_view.set_headers_visible(false);
+    tree_view.set_enable_search(false);
+    tree_view.set_show_expanders(true);
+
+    let favicon_column = TreeViewColumn::new();
+    let favicon_renderer = CellRendererPixbuf::new();
+    CellLayoutExt::pack_start(&favicon_column, &favicon_renderer, false);
+    // Both properties set the same image, so only one of them is set per row
+    CellLayoutExt::set_cell_data_func(
+        &favicon_column,
+        &favicon_renderer,
+        Some(std::boxed::Box::new(|_, cell, model, iter| {
+            let pixbuf = model
+                .value(iter, COLUMN_PIXBUF as i32)
+                .get::<Option<Pixbuf>>()
+                .ok()
+                .flatten();
+
+            match pixbuf {
+                Some(pixbuf) => cell.set_property("pixbuf", pixbuf),
+                None => {
+                    let icon_name = model
+                        .value(iter, COLUMN_ICON_NAME as i32)
+                        .get::<Option<String>>()
+                        .ok()
+                        .flatten();
+
+                    cell.set_property("icon-name", icon_name);
+                }
+            }
+        })),
+    );
+
+    let title_column = TreeViewColumn::new();
+    let title_renderer = CellRendererText::new();
+    title_renderer.set_ellipsize(gtk::pango::EllipsizeMode::End);
+    CellLayoutExt::pack_start(&title_column, &title_renderer, true);
+    CellLayoutExt::add_attribute(&title_column, &title_renderer, "text", COLUMN_TITLE as i32);
+    CellLayoutExt::add_attribute(
+        &title_column,
+        &title_renderer,
+        "foreground",
+        COLUMN_FOREGROUND as i32,
+    );
+
+    tree_view.append_column(&favicon_column);
+    tree_view.append_column(&title_column);
+
+    let scrolled_window = ScrolledWindow::new(gtk::Adjustment::NONE, gtk::Adjustment::NONE);
+    scrolled_window.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+    scrolled_window.add(&tree_view);
+
+    sidebar.pack_start(&header, false, false, 5);
+    sidebar.pack_start(&scrolled_window, true, true, 0);
+    sidebar.set_size_request(SIDEBAR_WIDTH, -1);
+
+    apply_css_style(
+        &[
+            sidebar.upcast_ref(),
+            tree_view.upcast_ref(),
+            collapse_button.upcast_ref(),
+            new_tab_button.upcast_ref(),
+        ],
+        "
+        box { background: #202020; }
+        treeview { background: #202020; color: #f1f1f1; }
+        treeview:selected { background: #2a2a2a; }
+        button { background: transparent; border: none; box-shadow: none; }
+        button:hover { background: #2a2a2a; }
+        ",
+    );
+
+    tree_view.selection().connect_changed({
+        let notebook = notebook.clone();
+        move |selection| {
+            if REBUILDING.with(|rebuilding| rebuilding.get()) {
+                return;
+            }
+
+            if let Some(page) = selection.selected().and_then(|(model, iter)| {
+                model.value(&iter, COLUMN_PAGE as i32).get::<Widget>().ok()
+            }) {
+                notebook.set_current_page(notebook.page_num(&page));
+            }
+        }
+    });
+
+    tree_view.connect_button_press_event({
+        let notebook = notebook.clone();
+        move |tree_view, event| {
+            let (x, y) = event.position();
+
+            let (model, iter) = match tree_view
+                .path_at_pos(x as i32, y as i32)
+                .and_then(|(path, ..)| path)
+                .and_then(|path| {
+                    let model = tree_view.model()?;
+                    let iter = model.iter(&path)?;
+                    Some((model, iter))
+                }) {
+                Some(row) => row,
+                None => return Propagation::Proceed,
+            };
+
+            if let Some(group) = row_group(&model, &iter) {
+                return match event.button() {
+                    1 => {
+                        let collapsed = is_group_collapsed(&notebook, &group);
+                        set_group_collapsed(&notebook, &group, !collapsed);
+                        Propagation::Stop
+                    }
+                    3 => {
+                        show_group_menu(&notebook, &group, event);
+                        Propagation::Stop
+                    }
+                    _ => Propagation::Proceed,
+                };
+            }
+
+            match model.value(&iter, COLUMN_PAGE as i32).get::<Widget>() {
+                Ok(page) => tab_button_press(&page, event),
+                Err(_) => Propagation::Proceed,
+            }
+        }
+    });
+
+    collapse_button.connect_clicked({
+        let sidebar = sidebar.clone();
+        let title_column = title_column.clone();
+        move |button| {
+            // Collapsed, the sidebar only shows favicons
+            let collapsed = title_column.is_visible();
+
+            title_column.set_visible(!collapsed);
+            sidebar.set_size_request(if collapsed { -1 } else { SIDEBAR_WIDTH }, -1);
+            button.set_label(if collapsed { "»" } else { "«" });
+        }
+    });
+
+    new_tab_button.connect_clicked({
+        let notebook = notebook.clone();
+        move |_| {
+            if let Some(browser_window) = find_browser_window(&notebook) {
+                add_tab(&notebook, &browser_window.search_bar, None);
+            }
+        }
+    });
+
+    header.show_all();
+    scrolled_window.show_all();
+
+    // The sidebar is shown or hidden by apply_tab_layout, never by show_all
+    sidebar.set_no_show_all(true);
+
+    (sidebar, tree_view)
+}
+
+fn tab_button_press(page: &Widget, event: &gtk::gdk::EventButton) -> Propagation {
+    match event.button() {
+        2 => {
+            close_tab(page);
+            Propagation::Stop
+        }
+        3 => {
+            if let Some(page) = page.downcast_ref::<Box>() {
+                show_tab_menu(page, event);
+            }
+            Propagation::Stop
+        }
+        _ => Propagation::Proceed,
+    }
+}
+
+/// The group a row of the sidebar stands for, `None` for tab rows.
+fn row_group(model: &TreeModel, iter: &TreeIter) -> Option<TabGroup> {
+    let name = model
+        .value(iter, COLUMN_GROUP as i32)
+        .get::<Option<String>>()
+        .ok()
+        .flatten()?;
+    let color = model
+        .value(iter, COLUMN_FOREGROUND as i32)
+        .get::<String>()
+        .ok()?;
+
+    Some(TabGroup { name, color })
+}
+
+/// Switches a window between notebook tabs and the vertical sidebar.
+pub fn apply_tab_layout(browser_window: &BrowserWindow, vertical_tabs: bool) {
+    browser_window.notebook.set_show_tabs(!vertical_tabs);
+    browser_window.sidebar.set_visible(vertical_tabs);
+
+    if vertical_tabs {
+        rebuild_tab_sidebar(browser_window);
+    }
+}
+
+pub fn toggle_vertical_tabs() {
+    let mut settings = Settings::load();
+    settings.vertical_tabs = !settings.vertical_tabs;
+    settings.save();
+
+    for browser_window in browser_windows() {
+        apply_tab_layout(&browser_window, settings.vertical_tabs);
+    }
+}
+
+/// Queues a rebuild of the window's sidebar once the current event is handled.
+pub fn refresh_tab_sidebar(notebook: &Notebook) {
+    let notebook = notebook.clone();
+
+    gtk::glib::idle_add_local_once(move || {
+        if let Some(browser_window) = find_browser_window(&notebook) {
+            if browser_window.sidebar.is_visible() {
+                rebuild_tab_sidebar(&browser_window);
+            }
+        }
+    });
+}
+
+fn rebuild_tab_sidebar(browser_window: &BrowserWindow) {
+    let store = match browser_window
+        .tab_tree
+        .model()
+        .and_then(|model| model.downcast::<TreeStore>().ok())
+    {
+        Some(store) => store,
+        None => return,
+    };
+
+    REBUILDING.with(|rebuilding| rebuilding.set(true));
+
+    store.clear();
+
+    let notebook = &browser_window.notebook;
+    let current_page = notebook.nth_page(notebook.current_page());
+    let mut inserted: Vec<(Widget, TreeIter)> = Vec::new();
+    let mut group_rows: Vec<(TabGroup, TreeIter)> = Vec::new();
+
+    for page in notebook.children() {
+        let state = match tab_state(&page) {
+            Some(state) => state,
+            None => continue,
+        };
+
+        let group = tab_group(&page);
+
+        // The notebook header with the group chips is hidden, so groups get
+        // rows of their own with their tabs nested under them
+        let group_row =
+            group.as_ref().map(
+                |group| match group_rows.iter().find(|(other, _)| other == group) {
+                    Some((_, iter)) => *iter,
+                    None => {
+                        let iter = append_group_row(&store, notebook, group);
+                        group_rows.push((group.clone(), iter));
+                        iter
+                    }
+                },
+            );
+
+        // Pages hidden by a collapsed group stay out of the list too
+        if !page.is_visible() {
+            continue;
+        }
+
+        // Tabs only nest under their opener within the same group
+        let parent = tab_opener(&page)
+            .filter(|opener| tab_group(opener) == group)
+            .and_then(|opener| {
+                inserted
+                    .iter()
+                    .find(|(inserted_page, _)| *inserted_page == opener)
+                    .map(|(_, iter)| *iter)
+            })
+            .or(group_row);
+
+        let iter = store.append(parent.as_ref());
+
+        store.set(
+            &iter,
+            &[
+                (COLUMN_PIXBUF, &state.favicon.pixbuf()),
+                (COLUMN_ICON_NAME, &"text-html"),
+                (COLUMN_TITLE, &tab_title(&page)),
+                (COLUMN_FOREGROUND, &row_color(&page, &state)),
+                (COLUMN_PAGE, &page),
+            ],
+        );
+
+        inserted.push((page, iter));
+    }
+
+    browser_window.tab_tree.expand_all();
+
+    if let Some((_, iter)) = inserted
+        .iter()
+        .find(|(page, _)| current_page.as_ref() == Some(page))
+    {
+        browser_window.tab_tree.selection().select_iter(iter);
+    }
+
+    REBUILDING.with(|rebuilding| rebuilding.set(false));
+}
+
+fn append_group_row(store: &TreeStore, notebook: &Notebook, group: &TabGroup) -> TreeIter {
+    let collapsed = is_group_collapsed(notebook, group);
+    let iter = store.append(None);
+
+    store.set(
+        &iter,
+        &[
+            (
+                COLUMN_TITLE,
+                &format!("{} {}", if collapsed { "▸" } else { "▾" }, group.name),
+            ),
+            (COLUMN_FOREGROUND, &group.color),
+            (COLUMN_GROUP, &group.name),
+        ],
+    );
+
+    iter
+}
+
+fn row_color(page: &Widget, state: &TabState) -> String {
+    if state.suspended.borrow().is_some() {
+        return "#888888".to_string();
+    }
+
+    tab_group(page)
+        .map(|group| group.color)
+        .unwrap_or_else(|| "#f1f1f1".to_string())
+}
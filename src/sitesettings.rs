@@ -0,0 +1,198 @@
+use gtk::prelude::*;
+use gtk::{Box, Switch};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::HashMap;
+use url::Url;
+use webkit2gtk::{LoadEvent, WebView, WebViewExt};
+
+use crate::settings::{create_settings_list, Settings, SettingsListRow};
+use crate::storage::{load_json, save_json};
+use crate::tabs::tab_browser_window;
+use crate::webview::{change_webview_setting, WebviewSetting};
+
+thread_local! {
+    // Set while the shield switches are moved to a tab's values, so that
+    // doesn't save an override
+    static SYNCING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Settings the user changed for single sites, by host.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SiteSettings {
+    pub hosts: HashMap<String, HashMap<WebviewSetting, bool>>,
+}
+
+impl SiteSettings {
+    pub fn save(&self) {
+        save_json("site_settings.json", self);
+    }
+
+    pub fn load() -> SiteSettings {
+        load_json("site_settings.json")
+    }
+}
+
+fn uri_host(uri: &str) -> Option<String> {
+    Url::parse(uri)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+}
+
+/// The value `setting` has on `host`: its override in `site_settings`, or the
+/// one from `settings`.
+fn effective_site_setting(
+    site_settings: &SiteSettings,
+    settings: &Settings,
+    host: Option<&str>,
+    setting: WebviewSetting,
+) -> bool {
+    host.and_then(|host| site_settings.hosts.get(host))
+        .and_then(|overrides| overrides.get(&setting).copied())
+        .unwrap_or_else(|| setting.default_value(settings))
+}
+
+fn apply_site_settings(webview: &WebView) {
+    let host = webview.uri().as_deref().and_then(uri_host);
+    let site_settings = SiteSettings::load();
+    let settings = Settings::load();
+
+    for setting in WebviewSetting::ALL {
+        change_webview_setting(
+            webview,
+            setting,
+            effective_site_setting(&site_settings, &settings, host.as_deref(), setting),
+        );
+    }
+}
+
+/// Saves `value` for the site in `webview` and reloads it with the change.
+/// Setting it back to the value from settings removes the override.
+pub fn set_site_setting(webview: &WebView, setting: WebviewSetting, value: bool) {
+    let host = match webview.uri().as_deref().and_then(uri_host) {
+        Some(host) => host,
+        None => {
+            // Pages without a host only get it until they are left
+            change_webview_setting(webview, setting, value);
+            return;
+        }
+    };
+
+    let mut site_settings = SiteSettings::load();
+    let overrides = site_settings.hosts.entry(host.clone()).or_default();
+
+    if value == setting.default_value(&Settings::load()) {
+        overrides.remove(&setting);
+    } else {
+        overrides.insert(setting, value);
+    }
+
+    if overrides.is_empty() {
+        site_settings.hosts.remove(&host);
+    }
+
+    site_settings.save();
+
+    change_webview_setting(webview, setting, value);
+    webview.reload();
+}
+
+pub fn is_syncing_site_switches() -> bool {
+    SYNCING.with(|syncing| syncing.get())
+}
+
+/// Moves the shield popover switches to the values used in `webview`.
+pub fn sync_site_switches(switches: &[(WebviewSetting, Switch)], webview: Option<&WebView>) {
+    let host = webview
+        .and_then(|webview| webview.uri())
+        .as_deref()
+        .and_then(uri_host);
+
+    let site_settings = SiteSettings::load();
+    let settings = Settings::load();
+
+    SYNCING.with(|syncing| syncing.set(true));
+
+    for (setting, switch) in switches {
+        switch.set_active(effective_site_setting(
+            &site_settings,
+            &settings,
+            host.as_deref(),
+            *setting,
+        ));
+    }
+
+    SYNCING.with(|syncing| syncing.set(false));
+}
+
+/// Applies the overrides for the host `webview` is about to load, before the
+/// new page commits, and keeps the shield switches in step.
+pub fn connect_site_settings(webview: &WebView) {
+    webview.connect_load_changed(|webview, event| match event {
+        LoadEvent::Started | LoadEvent::Redirected => apply_site_settings(webview),
+        LoadEvent::Committed => {
+            let page = match webview.parent() {
+                Some(page) => page,
+                None => return,
+            };
+
+            if let Some(browser_window) = tab_browser_window(&page) {
+                let notebook = &browser_window.notebook;
+
+                if notebook.nth_page(notebook.current_page()).as_ref() == Some(&page) {
+                    sync_site_switches(&browser_window.site_switches, Some(webview));
+                }
+            }
+        }
+        _ => {}
+    });
+}
+
+/// Lists the site overrides in the settings window, each with a button to
+/// remove it.
+pub fn create_site_settings_section(parent_box: &Box) {
+    let mut entries: Vec<(String, WebviewSetting, bool)> = SiteSettings::load()
+        .hosts
+        .into_iter()
+        .flat_map(|(host, overrides)| {
+            overrides
+                .into_iter()
+                .map(move |(setting, value)| (host.clone(), setting, value))
+        })
+        .collect();
+    entries.sort();
+
+    let rows = entries
+        .into_iter()
+        .map(|(host, setting, value)| SettingsListRow {
+            label: host.clone(),
+            detail: Some(format!(
+                "{}: {}",
+                setting.title(),
+                if value { "On" } else { "Off" }
+            )),
+            key: (host, setting),
+        })
+        .collect();
+
+    create_settings_list(
+        parent_box,
+        "Site overrides",
+        "No site overrides",
+        ("Remove", None),
+        rows,
+        |(host, setting)| {
+            let mut site_settings = SiteSettings::load();
+
+            if let Some(overrides) = site_settings.hosts.get_mut(host) {
+                overrides.remove(setting);
+
+                if overrides.is_empty() {
+                    site_settings.hosts.remove(host);
+                }
+            }
+
+            site_settings.save();
+        },
+    );
+}
@@ -0,0 +1,490 @@
+use gtk::gio::MemoryInputStream;
+use gtk::glib::Bytes;
+use gtk::prelude::*;
+use gtk::Widget;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use url::Url;
+use webkit2gtk::{
+    URIResponseExt, URISchemeRequest, URISchemeRequestExt, WebResource, WebResourceExt, WebViewExt,
+};
+
+use crate::reader::escape_html;
+use crate::tabs::{add_tab, all_webviews, page_webview, tab_browser_window};
+
+thread_local! {
+    // Sources taken from loaded pages, by page URI, until their source tab
+    // asks for them
+    static PENDING_SOURCES: RefCell<HashMap<String, (Vec<u8>, bool)>> =
+        RefCell::new(HashMap::new());
+}
+
+const VIEW_SOURCE_SCRIPT: &str = r#"
+    document.getElementById("wrap").onclick = () => document.body.classList.toggle("wrap");
+"#;
+
+/// Opens the source of the page in `page` in a new tab. The source comes
+/// from the resource the webview already loaded, so nothing is fetched again.
+pub fn view_page_source(page: &Widget) {
+    let webview = match page_webview(page) {
+        Some(webview) => webview,
+        None => return,
+    };
+
+    let resource = match webview.main_resource() {
+        Some(resource) => resource,
+        None => return,
+    };
+
+    let uri = resource
+        .uri()
+        .map(|uri| uri.to_string())
+        .unwrap_or_default();
+    let is_html = resource_is_html(&resource);
+
+    let page = page.downgrade();
+
+    WebResourceExt::data(&resource, gtk::gio::Cancellable::NONE, move |result| {
+        let data = match result {
+            Ok(data) => data,
+            Err(err) => {
+                println!("Failed to get page source: {}", err);
+                return;
+            }
+        };
+
+        let browser_window = match page.upgrade().and_then(|page| tab_browser_window(&page)) {
+            Some(browser_window) => browser_window,
+            None => return,
+        };
+
+        PENDING_SOURCES.with(|sources| {
+            sources
+                .borrow_mut()
+                .insert(uri.clone(), (data.to_vec(), is_html))
+        });
+
+        add_tab(
+            &browser_window.notebook,
+            &browser_window.search_bar,
+            Some(&source_uri(&uri)),
+        );
+    });
+}
+
+/// The internal page showing the source of `uri`, like
+/// `abrw://source?uri=https%3A%2F%2Fexample.com%2F`.
+pub fn source_uri(uri: &str) -> String {
+    let mut url = Url::parse("abrw://source").expect("Failed to parse source URI");
+    url.query_pairs_mut().append_pair("uri", uri);
+    url.to_string()
+}
+
+/// Answers an abrw://source request, with the source handed over by
+/// `view_page_source` or taken from a tab still showing the page.
+pub fn serve_source_page(request: &URISchemeRequest) {
+    let uri = request
+        .uri()
+        .and_then(|uri| Url::parse(&uri).ok())
+        .and_then(|url| {
+            url.query_pairs()
+                .find(|(key, _)| key == "uri")
+                .map(|(_, value)| value.to_string())
+        })
+        .unwrap_or_default();
+
+    if let Some((data, is_html)) = PENDING_SOURCES.with(|sources| sources.borrow_mut().remove(&uri))
+    {
+        finish_source_request(request, &uri, &data, is_html);
+        return;
+    }
+
+    // Another tab may still show the page, after reloading the source tab
+    let resource = all_webviews()
+        .into_iter()
+        .filter(|webview| webview.uri().as_deref() == Some(uri.as_str()))
+        .find_map(|webview| webview.main_resource());
+
+    let resource = match resource {
+        Some(resource) => resource,
+        None => {
+            finish_unavailable_request(request, &uri);
+            return;
+        }
+    };
+
+    let is_html = resource_is_html(&resource);
+    let request = request.clone();
+
+    WebResourceExt::data(
+        &resource,
+        gtk::gio::Cancellable::NONE,
+        move |result| match result {
+            Ok(data) => finish_source_request(&request, &uri, &data, is_html),
+            Err(err) => {
+                println!("Failed to get page source: {}", err);
+                finish_unavailable_request(&request, &uri);
+            }
+        },
+    );
+}
+
+fn resource_is_html(resource: &WebResource) -> bool {
+    resource
+        .response()
+        .and_then(|response| response.mime_type())
+        .is_none_or(|mime_type| mime_type.contains("html"))
+}
+
+/// The page isn't fetched again, that would go without the session's cookies
+/// and could show something other than what was loaded.
+fn finish_unavailable_request(request: &URISchemeRequest, uri: &str) {
+    let message = "The source is not available anymore. \
+                   Open the page again and view its source from there.";
+    let bytes = Bytes::from_owned(source_html(uri, message).into_bytes());
+    let stream = MemoryInputStream::from_bytes(&bytes);
+
+    request.finish(&stream, bytes.len() as i64, Some("text/html"));
+}
+
+fn finish_source_request(request: &URISchemeRequest, uri: &str, data: &[u8], is_html: bool) {
+    let source = String::from_utf8_lossy(data);
+    let base = Url::parse(uri).ok();
+
+    let highlighted = if is_html {
+        highlight_html(&source, base.as_ref())
+    } else {
+        escape_html(&source)
+    };
+
+    let bytes = Bytes::from_owned(source_html(uri, &highlighted).into_bytes());
+    let stream = MemoryInputStream::from_bytes(&bytes);
+
+    request.finish(&stream, bytes.len() as i64, Some("text/html"));
+}
+
+fn source_html(uri: &str, highlighted: &str) -> String {
+    let lines: String = highlighted
+        .split('\n')
+        .map(|line| format!("<span class=\"line\">{}</span>\n", line))
+        .collect();
+
+    format!(
+        r##"
+    <!DOCTYPE html>
+    <html lang="en">
+    <head>
+        <meta charset="UTF-8">
+        <title>Source of {title}</title>
+        <style>
+            body, html {{
+                margin: 0;
+                background-color: #202020;
+                color: #f1f1f1;
+            }}
+            .toolbar {{
+                position: fixed;
+                top: 10px;
+                right: 10px;
+            }}
+            .toolbar button {{
+                background: #2a2a2a;
+                color: #f1f1f1;
+                border: none;
+                border-radius: 7px;
+                padding: 5px 10px;
+                cursor: pointer;
+            }}
+            pre {{
+                margin: 0;
+                padding: 10px 0;
+                font-family: "Hack", monospace;
+                font-size: 13px;
+                counter-reset: line;
+            }}
+            .wrap pre {{
+                white-space: pre-wrap;
+                word-break: break-all;
+            }}
+            .line {{
+                display: block;
+                position: relative;
+                padding-left: 70px;
+            }}
+            .line::before {{
+                counter-increment: line;
+                content: counter(line);
+                position: absolute;
+                left: 0;
+                width: 55px;
+                text-align: right;
+                color: #888;
+            }}
+            .tag {{ color: #81a1c1; }}
+            .attr {{ color: #8fbcbb; }}
+            .value {{ color: #a3be8c; }}
+            .comment {{ color: #888; }}
+            a {{ color: inherit; }}
+        </style>
+    </head>
+    <body>
+        <div class="toolbar">
+            <button id="wrap">Wrap lines</button>
+        </div>
+        <pre>{lines}</pre>
+        <script>{script}</script>
+    </body>
+    </html>
+    "##,
+        title = escape_html(uri),
+        lines = lines,
+        script = VIEW_SOURCE_SCRIPT,
+    )
+}
+
+/// Appends `text` wrapped in a span of `class`. Spans are closed at line
+/// breaks so every line of the output stays well formed on its own.
+fn push_span(out: &mut String, class: &str, text: &str) {
+    for (index, part) in text.split('\n').enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+
+        if !part.is_empty() {
+            out.push_str(&format!(
+                "<span class=\"{}\">{}</span>",
+                class,
+                escape_html(part)
+            ));
+        }
+    }
+}
+
+/// Marks up tags, attributes, values and comments of an HTML document.
+fn highlight_html(source: &str, base: Option<&Url>) -> String {
+    // Lowercasing keeps byte offsets, so end tags are looked up in this copy
+    let lowercase = source.to_ascii_lowercase();
+    let mut out = String::new();
+    let mut rest = source;
+
+    while !rest.is_empty() {
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |end| end + 3);
+            push_span(&mut out, "comment", &rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+
+        let is_tag = rest.starts_with('<')
+            && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!');
+
+        if is_tag {
+            let end = tag_end(rest);
+            let name = highlight_tag(&mut out, &rest[..end], base);
+            rest = &rest[end..];
+
+            // Scripts and styles may contain anything up to their end tag
+            if name == "script" || name == "style" {
+                let closing = format!("</{}", name);
+                let end = lowercase[source.len() - rest.len()..]
+                    .find(&closing)
+                    .unwrap_or(rest.len());
+                out.push_str(&escape_html(&rest[..end]));
+                rest = &rest[end..];
+            }
+            continue;
+        }
+
+        let first = rest.chars().next().map_or(1, |c| c.len_utf8());
+        let end = rest[first..]
+            .find('<')
+            .map_or(rest.len(), |end| end + first);
+        out.push_str(&escape_html(&rest[..end]));
+        rest = &rest[end..];
+    }
+
+    out
+}
+
+/// Length of the tag at the start of `source`, up to and including its `>`.
+fn tag_end(source: &str) -> usize {
+    let mut quote = None;
+
+    for (index, byte) in source.bytes().enumerate() {
+        match (quote, byte) {
+            (None, b'>') => return index + 1,
+            (None, b'"' | b'\'') => quote = Some(byte),
+            (Some(open), _) if open == byte => quote = None,
+            _ => {}
+        }
+    }
+
+    source.len()
+}
+
+/// Marks up a single tag and returns its lowercased name.
+fn highlight_tag(out: &mut String, tag: &str, base: Option<&Url>) -> String {
+    let bytes = tag.as_bytes();
+    let is_separator = |byte: u8| byte.is_ascii_whitespace() || byte == b'>';
+
+    let mut i = 1;
+    while i < bytes.len() && !is_separator(bytes[i]) && !(bytes[i] == b'/' && i > 1) {
+        i += 1;
+    }
+
+    push_span(out, "tag", &tag[..i]);
+    let name = tag[1..i].to_ascii_lowercase();
+
+    while i < bytes.len() {
+        let start = i;
+
+        if bytes[i].is_ascii_whitespace() {
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            out.push_str(&tag[start..i]);
+            continue;
+        }
+
+        if matches!(bytes[i], b'>' | b'/' | b'=') {
+            push_span(out, "tag", &tag[i..i + 1]);
+            i += 1;
+            continue;
+        }
+
+        while i < bytes.len() && !is_separator(bytes[i]) && !matches!(bytes[i], b'/' | b'=') {
+            i += 1;
+        }
+
+        let attribute = &tag[start..i];
+        push_span(out, "attr", attribute);
+
+        let mut j = i;
+        while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+            j += 1;
+        }
+
+        if j >= bytes.len() || bytes[j] != b'=' {
+            continue;
+        }
+
+        j += 1;
+        while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+            j += 1;
+        }
+
+        out.push_str(&tag[i..j]);
+
+        let end = match bytes.get(j) {
+            Some(&quote @ (b'"' | b'\'')) => tag[j + 1..]
+                .bytes()
+                .position(|byte| byte == quote)
+                .map_or(bytes.len(), |end| j + end + 2),
+            _ => (j..bytes.len())
+                .find(|&end| is_separator(bytes[end]))
+                .unwrap_or(bytes.len()),
+        };
+
+        push_value(out, attribute, &tag[j..end], base);
+        i = end;
+    }
+
+    name
+}
+
+/// Appends an attribute value, as a link when it points at another resource.
+fn push_value(out: &mut String, attribute: &str, value: &str, base: Option<&Url>) {
+    let target = value
+        .trim_matches(|c| c == '"' || c == '\'')
+        .replace("&amp;", "&");
+
+    let link = match attribute.to_ascii_lowercase().as_str() {
+        "href" | "src" if !value.contains('\n') => base
+            .and_then(|base| base.join(&target).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https" | "file")),
+        _ => None,
+    };
+
+    match link {
+        Some(url) => {
+            out.push_str(&format!("<a href=\"{}\">", escape_html(url.as_str())));
+            push_span(out, "value", value);
+            out.push_str("</a>");
+        }
+        None => push_span(out, "value", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_up_tags_attributes_and_values() {
+        assert_eq!(
+            highlight_html("<p class=\"x\">Hi</p>", None),
+            "<span class=\"tag\">&lt;p</span> <span class=\"attr\">class</span>=\
+             <span class=\"value\">&quot;x&quot;</span>\
+             <span class=\"tag\">&gt;</span>Hi<span class=\"tag\">&lt;/p</span>\
+             <span class=\"tag\">&gt;</span>"
+        );
+    }
+
+    #[test]
+    fn marks_up_comments() {
+        assert_eq!(
+            highlight_html("<!-- a < b -->", None),
+            "<span class=\"comment\">&lt;!-- a &lt; b --&gt;</span>"
+        );
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(highlight_html("a < b & c", None), "a &lt; b &amp; c");
+    }
+
+    #[test]
+    fn leaves_script_contents_alone() {
+        let highlighted = highlight_html("<script>if (a<b) {}</SCRIPT><p>", None);
+
+        assert!(highlighted.contains("if (a&lt;b) {}"));
+        assert!(highlighted
+            .ends_with("<span class=\"tag\">&lt;p</span><span class=\"tag\">&gt;</span>"));
+    }
+
+    #[test]
+    fn finds_end_tags_after_non_ascii_text() {
+        let highlighted = highlight_html("<p>ä</p><style>p<q</style>ö", None);
+
+        assert!(highlighted.contains("p&lt;q<span class=\"tag\">&lt;/style</span>"));
+        assert!(highlighted.ends_with('ö'));
+    }
+
+    #[test]
+    fn links_resources_against_the_base() {
+        let base = Url::parse("https://example.com/dir/page.html").unwrap();
+        let highlighted = highlight_html("<img src=\"pic.png\" alt=\"x\">", Some(&base));
+
+        assert!(highlighted.contains("<a href=\"https://example.com/dir/pic.png\">"));
+        assert_eq!(highlighted.matches("<a href").count(), 1);
+    }
+
+    #[test]
+    fn doesnt_link_other_schemes() {
+        let base = Url::parse("https://example.com/").unwrap();
+
+        assert!(
+            !highlight_html("<a href=\"javascript:alert(1)\">", Some(&base)).contains("<a href")
+        );
+    }
+
+    #[test]
+    fn keeps_every_line_well_formed() {
+        let highlighted = highlight_html("<!-- one\ntwo -->", None);
+
+        assert_eq!(
+            highlighted,
+            "<span class=\"comment\">&lt;!-- one</span>\n<span class=\"comment\">two --&gt;</span>"
+        );
+    }
+}
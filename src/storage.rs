@@ -0,0 +1,20 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+
+/// Writes `value` as JSON to `path`, relative to the working directory.
+pub fn save_json<T: Serialize>(path: &str, value: &T) {
+    let json_data = serde_json::to_string(value)
+        .unwrap_or_else(|err| panic!("Failed to serialize {}: {}", path, err));
+    fs::write(path, json_data).unwrap_or_else(|err| panic!("Failed to write {}: {}", path, err));
+}
+
+/// Reads `path` written by `save_json`. A missing or unreadable file gives
+/// the default value.
+pub fn load_json<T: DeserializeOwned + Default>(path: &str) -> T {
+    if let Ok(data) = fs::read_to_string(path) {
+        serde_json::from_str(&data).unwrap_or_default()
+    } else {
+        T::default()
+    }
+}
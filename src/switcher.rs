@@ -0,0 +1,157 @@
+use gtk::prelude::*;
+use gtk::{Box, Label, Notebook, Widget, Window};
+
+use crate::browser_windows;
+use crate::groups::select_tab;
+use crate::popuplist::show_popup_list;
+use crate::styles::apply_css_style;
+use crate::tabs::{tab_title, tab_uri};
+
+#[derive(Clone)]
+struct SwitcherTab {
+    notebook: Notebook,
+    page: Widget,
+    title: String,
+    uri: String,
+}
+
+/// Popup listing the tabs of every window, filtered as you type.
+pub fn show_tab_switcher(parent: &Window) {
+    let tabs: Vec<SwitcherTab> = browser_windows()
+        .iter()
+        .flat_map(|browser_window| {
+            let notebook = browser_window.notebook.clone();
+
+            notebook
+                .children()
+                .into_iter()
+                .map(move |page| SwitcherTab {
+                    notebook: notebook.clone(),
+                    title: tab_title(&page),
+                    uri: tab_uri(&page).unwrap_or_default(),
+                    page,
+                })
+        })
+        .collect();
+
+    show_popup_list(
+        parent,
+        "Switch tab",
+        "Search tabs",
+        tabs,
+        |query, tab| {
+            fuzzy_score(query, &tab.title)
+                .into_iter()
+                .chain(fuzzy_score(query, &tab.uri))
+                .max()
+        },
+        create_switcher_row,
+        activate_tab,
+    );
+}
+
+fn create_switcher_row(tab: &SwitcherTab) -> Box {
+    let row_box = Box::new(gtk::Orientation::Vertical, 2);
+
+    let title_label = Label::new(Some(&tab.title));
+    let uri_label = Label::new(Some(&tab.uri));
+
+    for label in [&title_label, &uri_label] {
+        label.set_halign(gtk::Align::Start);
+        label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+    }
+
+    apply_css_style(&[uri_label.upcast_ref()], "label { color: #888; }");
+
+    row_box.pack_start(&title_label, false, false, 0);
+    row_box.pack_start(&uri_label, false, false, 0);
+
+    row_box
+}
+
+fn activate_tab(tab: &SwitcherTab) {
+    select_tab(&tab.notebook, &tab.page);
+
+    if let Some(window) = tab
+        .notebook
+        .toplevel()
+        .and_then(|toplevel| toplevel.downcast::<Window>().ok())
+    {
+        window.present();
+    }
+}
+
+/// Scores `text` against `query` when every query character appears in order.
+/// Consecutive matches and matches at word starts score higher.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for query_char in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = text[position..]
+            .iter()
+            .position(|c| *c == query_char)
+            .map(|offset| position + offset)?;
+
+        score += 1;
+
+        if previous_match.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+
+        previous_match = Some(found);
+        position = found + 1;
+    }
+
+    Some(score - previous_match.map_or(0, |last| last as i32 / 10))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_characters_in_order() {
+        assert!(fuzzy_score("gh", "GitHub").is_some());
+        assert!(fuzzy_score("ghb", "github").is_some());
+        assert!(fuzzy_score("hg", "github").is_none());
+        assert!(fuzzy_score("x", "github").is_none());
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("  ", ""), Some(0));
+    }
+
+    #[test]
+    fn ignores_case_and_whitespace_in_query() {
+        assert_eq!(
+            fuzzy_score("Git Hub", "github"),
+            fuzzy_score("github", "github")
+        );
+    }
+
+    #[test]
+    fn consecutive_matches_score_higher() {
+        assert!(fuzzy_score("git", "github") > fuzzy_score("git", "g_i_t"));
+    }
+
+    #[test]
+    fn word_starts_score_higher() {
+        assert!(fuzzy_score("n", "new tab") > fuzzy_score("n", "open"));
+        assert!(fuzzy_score("t", "new tab") > fuzzy_score("t", "quit"));
+    }
+
+    #[test]
+    fn earlier_matches_score_higher() {
+        let long_prefix = format!("{}github", "x".repeat(40));
+        assert!(fuzzy_score("github", "github") > fuzzy_score("github", &long_prefix));
+    }
+}
@@ -1,117 +1,194 @@
-use crate::webview::create_webview;
-use crate::{create_window, styles::apply_css_style};
+use crate::bookmarks::Bookmarks;
+use crate::browser_windows;
+use crate::devtools::connect_inspector;
+use crate::errorpage::show_error_page;
+use crate::findbar::{connect_find_controller, FindState};
+use crate::groups::{append_group_menu_items, TabGroup};
+use crate::history::record_visit;
+use crate::https::{connect_https_upgrades, show_https_fallback, HttpsUpgrade};
+use crate::internal::NEWTAB_URI;
+use crate::newtab::new_tab_uri;
+use crate::permissions::connect_permission_requests;
+use crate::reader::{connect_reader_messages, reset_reader_state, ReaderState};
+use crate::security::connect_security_indicator;
+use crate::session::save_session;
+use crate::settings::Settings;
+use crate::sidebar::refresh_tab_sidebar;
+use crate::sitesettings::connect_site_settings;
+use crate::source::view_page_source;
+use crate::styles::apply_css_style;
+use crate::tls::{connect_tls_errors, PendingTlsError};
+use crate::vim::{connect_vim_messages, VimMode};
+use crate::webview::{create_webview, FORM_INPUT_MESSAGE};
+use crate::zoom::{apply_site_zoom, connect_zoom_indicator};
+use crate::{create_empty_window, create_window, find_browser_window, BrowserWindow};
+use gtk::gdk_pixbuf::{InterpType, Pixbuf};
+use gtk::glib::{Propagation, WeakRef};
 use gtk::prelude::*;
-use gtk::{gio::SimpleAction, Box, Button, Entry, Label, Notebook};
+use gtk::{
+    gio::SimpleAction, Box, Button, CssProvider, Entry, EventBox, Image, Label, Notebook, Widget,
+};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use webkit2gtk::{
-    ContextMenu, ContextMenuAction, ContextMenuExt, ContextMenuItem, ContextMenuItemExt,
-    HitTestResultExt, WebViewExt,
+    BackForwardListExt, ContextMenu, ContextMenuAction, ContextMenuExt, ContextMenuItem,
+    ContextMenuItemExt, HitTestResultExt, LoadEvent, NetworkError, PolicyError,
+    UserContentManagerExt, WebView, WebViewExt, WebViewSessionState,
 };
 
-const HOME_PAGE_HTML: &str = r##"
-    <!DOCTYPE html>
-    <html lang="en">
-    <head>
-        <meta charset="UTF-8">
-        <meta name="viewport" content="width=device-width, initial-scale=1.0">
-        <title>New tab</title>
-        <style>
-            body, html {
-                height: 100%;
-                margin: 0;
-                display: flex;
-                justify-content: center;
-                align-items: center;
-                background-color: #202020;
-            }
-            .container {
-                text-align: center;
-            }
-            svg {
-                width: 100px;
-                height: 100px;
-                margin-bottom: 20px;
-            }
-            .darkened {
-                color: #888;
-            }
-            .hidden {
-                display: none;
-            }
-            h1 {
-                color: #f1f1f1;
-            }
-        </style>
-    </head>
-    <body>
-        <div class="container">
-            <div id="svg1" class="hidden">
-                <svg xmlns="http://www.w3.org/2000/svg" width="28" height="28" viewBox="0 0 24 24" fill="none" stroke="#ffffff" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" class="lucide lucide-radar"><path d="M19.07 4.93A10 10 0 0 0 6.99 3.34"/><path d="M4 6h.01"/><path d="M2.29 9.62A10 10 0 1 0 21.31 8.35"/><path d="M16.24 7.76A6 6 0 1 0 8.23 16.67"/><path d="M12 18h.01"/><path d="M17.99 11.66A6 6 0 0 1 15.77 16.67"/><circle cx="12" cy="12" r="2"/><path d="m13.41 10.59 5.66-5.66"/></svg>
-            </div>
-            <div id="svg2" class="hidden">
-                <svg xmlns="http://www.w3.org/2000/svg" width="28" height="28" viewBox="0 0 24 24" fill="none" stroke="#ffffff" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" class="lucide lucide-coffee"><path d="M10 2v2"/><path d="M14 2v2"/><path d="M16 8a1 1 0 0 1 1 1v8a4 4 0 0 1-4 4H7a4 4 0 0 1-4-4V9a1 1 0 0 1 1-1h14a4 4 0 1 1 0 8h-1"/><path d="M6 2v2"/></svg>
-            </div>
-            <div id="svg3" class="hidden">
-                <svg xmlns="http://www.w3.org/2000/svg" width="28" height="28" viewBox="0 0 24 24" fill="none" stroke="#ffffff" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" class="lucide lucide-cookie"><path d="M12 2a10 10 0 1 0 10 10 4 4 0 0 1-5-5 4 4 0 0 1-5-5"/><path d="M8.5 8.5v.01"/><path d="M16 15.5v.01"/><path d="M12 12v.01"/><path d="M11 17v.01"/><path d="M7 14v.01"/></svg>
-            </div>
-
-            <div id="svg4" class="hidden">
-                <svg xmlns="http://www.w3.org/2000/svg" width="28" height="28" viewBox="0 0 24 24" fill="none" stroke="#ffffff" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" class="lucide lucide-bomb"><circle cx="11" cy="13" r="9"/><path d="M14.35 4.65 16.3 2.7a2.41 2.41 0 0 1 3.4 0l1.6 1.6a2.4 2.4 0 0 1 0 3.4l-1.95 1.95"/><path d="m22 2-1.5 1.5"/></svg>
-            </div>
-
-            <div id="svg5" class="hidden">
-                <svg xmlns="http://www.w3.org/2000/svg" width="28" height="28" viewBox="0 0 24 24" fill="none" stroke="#ffffff" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" class="lucide lucide-bowl-chopsticks"><path d="m13 2-3 11"/><path d="m22 2-8 11"/><ellipse cx="12" cy="12" rx="10" ry="5"/><path d="M22 12a10 10 0 0 1-20 0"/></svg>
-            </div>
-
-            <div id="svg6" class="hidden">
-                <svg xmlns="http://www.w3.org/2000/svg" width="28" height="28" viewBox="0 0 24 24" fill="none" stroke="#ffffff" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" class="lucide lucide-bot"><path d="M12 8V4H8"/><rect width="16" height="12" x="4" y="8" rx="2"/><path d="M2 14h2"/><path d="M20 14h2"/><path d="M15 13v2"/><path d="M9 13v2"/></svg>
-            </div>
-
-            <div id="svg7" class="hidden">
-                <svg xmlns="http://www.w3.org/2000/svg" width="28" height="28" viewBox="0 0 24 24" fill="none" stroke="#ffffff" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" class="lucide lucide-square-terminal"><path d="m7 11 2-2-2-2"/><path d="M11 13h4"/><rect width="18" height="18" x="3" y="3" rx="2" ry="2"/></svg>
-            </div>
-
-            <div id="svg8" class="hidden">
-                <svg xmlns="http://www.w3.org/2000/svg" width="28" height="28" viewBox="0 0 24 24" fill="none" stroke="#ffffff" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" class="lucide lucide-antenna"><path d="M2 12 7 2"/><path d="m7 12 5-10"/><path d="m12 12 5-10"/><path d="m17 12 5-10"/><path d="M4.5 7h15"/><path d="M12 16v6"/></svg>
-            </div>
-
-            <div id="random-svg-container"></div>
-
-            <h1>aapelix/abrw</h1>
-            <p class="darkened">F1 to open new tab</p>
-            <p class="darkened">F2 to open new window</p>
-        </div>
-
-        <script>
-            const svgIds = ['svg1', 'svg2', 'svg3', 'svg4', 'svg5', 'svg6', 'svg7', 'svg8'];
-
-            function getRandomSVG() {
-                const randomIndex = Math.floor(Math.random() * svgIds.length);
-                return svgIds[randomIndex];
-            }
+const TAB_STATE_KEY: &str = "abrw-tab-state";
+const TAB_WIDTH: i32 = 80;
+const FAVICON_SIZE: i32 = 16;
+const SUSPENDED_TAB_OPACITY: f64 = 0.5;
+
+/// State that belongs to a tab rather than to the page loaded in it.
+/// It is attached to the notebook page widget, so it follows the page around.
+pub struct TabState {
+    pub pinned: Cell<bool>,
+    pub suspended: RefCell<Option<SuspendedTab>>,
+    pub last_active: Cell<Instant>,
+    pub form_dirty: Cell<bool>,
+    pub vim_mode: Cell<VimMode>,
+    pub find: RefCell<FindState>,
+    pub reader: Cell<ReaderState>,
+    pub devtools_open: Cell<bool>,
+    pub tls_error: RefCell<Option<PendingTlsError>>,
+    pub mixed_content: Cell<bool>,
+    pub https_upgrade: RefCell<Option<HttpsUpgrade>>,
+    pub permission_prompts: RefCell<Vec<Box>>,
+    pub opener: RefCell<Option<WeakRef<Widget>>>,
+    pub group: RefCell<Option<TabGroup>>,
+    pub group_style: CssProvider,
+    pub tab_box: Box,
+    pub favicon: Image,
+    pub label: Label,
+    pub close_button: Button,
+}
 
-            function showRandomSVG() {
-                const randomSVGId = getRandomSVG();
-                const randomSVG = document.getElementById(randomSVGId);
-                const container = document.getElementById('random-svg-container');
+/// What is left of a tab after its webview has been discarded.
+pub struct SuspendedTab {
+    pub uri: Option<String>,
+    pub title: String,
+    pub session_state: Option<WebViewSessionState>,
+}
 
-                const clonedSVG = randomSVG.cloneNode(true);
-                clonedSVG.classList.remove('hidden');
+pub fn tab_state(page: &impl IsA<Widget>) -> Option<Rc<TabState>> {
+    unsafe {
+        page.data::<Rc<TabState>>(TAB_STATE_KEY)
+            .map(|state| state.as_ref().clone())
+    }
+}
 
-                container.appendChild(clonedSVG);
-            }
+pub fn is_tab_pinned(page: &impl IsA<Widget>) -> bool {
+    tab_state(page).is_some_and(|state| state.pinned.get())
+}
+
+pub fn pinned_tab_count(notebook: &Notebook) -> u32 {
+    notebook
+        .children()
+        .iter()
+        .filter(|page| is_tab_pinned(*page))
+        .count() as u32
+}
+
+pub fn page_notebook(page: &impl IsA<Widget>) -> Option<Notebook> {
+    page.parent()
+        .and_then(|parent| parent.downcast::<Notebook>().ok())
+}
+
+/// The webview shown in a tab page, or `None` while the tab is suspended.
+pub fn page_webview(page: &impl IsA<Widget>) -> Option<WebView> {
+    page.dynamic_cast_ref::<Box>()?
+        .children()
+        .into_iter()
+        .find_map(|child| child.downcast::<WebView>().ok())
+}
+
+/// The webviews of the tabs in every window, suspended tabs left out.
+pub fn all_webviews() -> Vec<WebView> {
+    browser_windows()
+        .iter()
+        .flat_map(|browser_window| browser_window.notebook.children())
+        .filter_map(|page| page_webview(&page))
+        .collect()
+}
+
+pub fn tab_title(page: &impl IsA<Widget>) -> String {
+    if let Some(suspended) = tab_state(page).and_then(|state| {
+        state
+            .suspended
+            .borrow()
+            .as_ref()
+            .map(|suspended| suspended.title.clone())
+    }) {
+        return suspended;
+    }
+
+    page_webview(page)
+        .and_then(|webview| webview.title())
+        .map(|title| title.to_string())
+        .unwrap_or_else(|| "Untitled".to_string())
+}
+
+pub fn tab_uri(page: &impl IsA<Widget>) -> Option<String> {
+    if let Some(state) = tab_state(page) {
+        if let Some(suspended) = state.suspended.borrow().as_ref() {
+            return suspended.uri.clone();
+        }
+    }
+
+    page_webview(page)
+        .and_then(|webview| webview.uri())
+        .map(|uri| uri.to_string())
+        .filter(|uri| uri != "about:blank")
+}
+
+/// The tab a tab was opened from with "Open Link in New Tab", if still open.
+pub fn tab_opener(page: &impl IsA<Widget>) -> Option<Widget> {
+    tab_state(page).and_then(|state| {
+        state
+            .opener
+            .borrow()
+            .as_ref()
+            .and_then(|opener| opener.upgrade())
+    })
+}
+
+/// The window a tab currently lives in. Tabs can move between windows, so
+/// this is looked up each time instead of being captured when the tab is made.
+pub fn tab_browser_window(page: &impl IsA<Widget>) -> Option<BrowserWindow> {
+    page_notebook(page).and_then(|notebook| find_browser_window(&notebook))
+}
+
+pub fn add_tab(notebook: &Notebook, search_entry: &Entry, uri: Option<&str>) -> Box {
+    let webview = create_webview();
+
+    match uri {
+        Some(uri) => {
+            webview.load_uri(uri);
+        }
+        None => {
+            webview.load_uri(&new_tab_uri());
+        }
+    }
 
-            showRandomSVG();
-        </script>
-    </body>
-    </html>
+    append_tab(notebook, search_entry, &webview)
+}
 
-    "##;
+/// Wraps `webview` in a tab page with its own tab label and appends it to
+/// `notebook` as the current page.
+fn append_tab(notebook: &Notebook, search_entry: &Entry, webview: &WebView) -> Box {
+    let page = Box::new(gtk::Orientation::Vertical, 0);
 
-pub fn add_tab(notebook: &Notebook, search_entry: &Entry, uri: Option<&str>) {
+    let tab_event_box = EventBox::new();
     let tab_box = Box::new(gtk::Orientation::Horizontal, 5);
+    let tab_favicon = Image::from_icon_name(Some("text-html"), gtk::IconSize::Menu);
     let tab_label = Label::new(Some("New tab"));
 
-    tab_box.set_size_request(-1, 15);
+    tab_box.set_size_request(TAB_WIDTH, 15);
+    tab_event_box.set_visible_window(false);
+    tab_event_box.add(&tab_box);
 
     let close_button = Button::with_label("x");
 
@@ -127,31 +204,127 @@ pub fn add_tab(notebook: &Notebook, search_entry: &Entry, uri: Option<&str>) {
 
     close_button.set_size_request(10, 10);
 
+    tab_box.pack_start(&tab_favicon, false, false, 0);
     tab_box.pack_start(&tab_label, false, false, 0);
     tab_box.pack_end(&close_button, false, false, 0);
 
-    let webview = create_webview();
+    // Filled in with the group color once the tab joins a group
+    let group_style = CssProvider::new();
+    tab_box
+        .style_context()
+        .add_provider(&group_style, gtk::STYLE_PROVIDER_PRIORITY_USER);
+
+    unsafe {
+        page.set_data(
+            TAB_STATE_KEY,
+            Rc::new(TabState {
+                pinned: Cell::new(false),
+                suspended: RefCell::new(None),
+                last_active: Cell::new(Instant::now()),
+                form_dirty: Cell::new(false),
+                vim_mode: Cell::new(VimMode::Normal),
+                find: RefCell::new(FindState::default()),
+                reader: Cell::new(ReaderState::default()),
+                devtools_open: Cell::new(false),
+                tls_error: RefCell::new(None),
+                mixed_content: Cell::new(false),
+                https_upgrade: RefCell::new(None),
+                permission_prompts: RefCell::new(Vec::new()),
+                opener: RefCell::new(None),
+                group: RefCell::new(None),
+                group_style: group_style.clone(),
+                tab_box: tab_box.clone(),
+                favicon: tab_favicon.clone(),
+                label: tab_label.clone(),
+                close_button: close_button.clone(),
+            }),
+        );
+    }
 
-    match uri {
-        Some(uri) => {
-            webview.load_uri(uri);
+    attach_webview(&page, webview);
+
+    let tab_index = notebook.append_page(&page, Some(&tab_event_box));
+
+    page.show();
+    tab_box.show();
+    tab_favicon.show();
+    tab_label.show();
+    close_button.show();
+
+    notebook.set_current_page(Some(tab_index));
+
+    // The tab label lives as long as the page, so it only holds a weak reference
+    close_button.connect_clicked({
+        let page = page.downgrade();
+        move |_| {
+            if let Some(page) = page.upgrade() {
+                close_tab(&page);
+            }
         }
-        None => {
-            webview.load_html(HOME_PAGE_HTML, None);
+    });
+
+    tab_event_box.connect_button_press_event({
+        let page = page.downgrade();
+        move |_, event| {
+            let page = match page.upgrade() {
+                Some(page) => page,
+                None => return Propagation::Proceed,
+            };
+
+            match event.button() {
+                2 => {
+                    close_tab(&page);
+                    Propagation::Stop
+                }
+                3 => {
+                    show_tab_menu(&page, event);
+                    Propagation::Stop
+                }
+                _ => Propagation::Proceed,
+            }
         }
-    }
+    });
+
+    search_entry.set_is_focus(true);
+
+    page
+}
+
+/// Puts `webview` into `page` and hooks it up to the page's tab label.
+fn attach_webview(page: &Box, webview: &WebView) {
+    let state = match tab_state(page) {
+        Some(state) => state,
+        None => return,
+    };
+
+    page.pack_start(webview, true, true, 0);
+    webview.show();
 
-    let search_entry_clone = search_entry.clone();
-    webview.connect_notify_local(Some("uri"), move |webview, _| {
-        if let Some(uri) = webview.uri() {
-            search_entry_clone.set_text(&uri);
+    webview.connect_notify_local(Some("uri"), {
+        let page = page.downgrade();
+        move |webview, _| {
+            let page = match page.upgrade() {
+                Some(page) => page,
+                None => return,
+            };
+
+            if let (Some(uri), Some(browser_window)) = (webview.uri(), tab_browser_window(&page)) {
+                let notebook = &browser_window.notebook;
+
+                if notebook.current_page().is_some()
+                    && notebook.current_page() == notebook.page_num(&page)
+                {
+                    browser_window.search_bar.set_text(&uri);
+                }
+            }
+
+            save_session();
         }
     });
 
-    let notebook_clone = notebook.clone();
+    let tab_label_clone = state.label.clone();
+    let tab_box_clone = state.tab_box.clone();
     webview.connect_title_notify(move |webview| {
-        let notebook = notebook_clone.clone();
-        let webview = webview.clone();
         let max_length = 15;
 
         let title = webview
@@ -167,79 +340,82 @@ pub fn add_tab(notebook: &Notebook, search_entry: &Entry, uri: Option<&str>) {
             truncated_title
         };
 
-        let current_page = notebook.current_page();
+        tab_label_clone.set_label(&final_title);
+        tab_box_clone.set_tooltip_text(Some(&title));
 
-        if let Some(page) = notebook.nth_page(current_page) {
-            if let Some(tab) = notebook.tab_label(&page) {
-                if let Some(tab_box) = tab.downcast_ref::<gtk::Container>() {
-                    for child in tab_box.children() {
-                        if let Some(label) = child.downcast_ref::<gtk::Label>() {
-                            label.set_label(&final_title);
-                        }
-                    }
+        if let Some(notebook) = webview.parent().as_ref().and_then(page_notebook) {
+            refresh_tab_sidebar(&notebook);
+        }
+    });
+
+    let tab_favicon_clone = state.favicon.clone();
+    webview.connect_favicon_notify(move |webview| {
+        match webview
+            .favicon()
+            .and_then(|surface| favicon_pixbuf(&surface))
+        {
+            Some(pixbuf) => tab_favicon_clone.set_from_pixbuf(Some(&pixbuf)),
+            None => tab_favicon_clone.set_from_icon_name(Some("text-html"), gtk::IconSize::Menu),
+        }
+
+        if let Some(notebook) = webview.parent().as_ref().and_then(page_notebook) {
+            refresh_tab_sidebar(&notebook);
+        }
+    });
+
+    if let Some(content_manager) = webview.user_content_manager() {
+        let state = state.clone();
+        content_manager.connect_script_message_received(Some(FORM_INPUT_MESSAGE), move |_, _| {
+            state.form_dirty.set(true);
+        });
+    }
+
+    connect_vim_messages(webview, state.clone());
+    connect_find_controller(webview, state.clone());
+    connect_reader_messages(webview, state.clone());
+    connect_inspector(webview, state.clone());
+    connect_https_upgrades(webview, state.clone());
+    connect_tls_errors(webview, state.clone());
+    connect_security_indicator(webview, state.clone());
+    connect_permission_requests(webview, state.clone());
+    connect_site_settings(webview);
+
+    webview.connect_load_changed({
+        let state = state.clone();
+        move |webview, event| {
+            if event == LoadEvent::Committed {
+                state.form_dirty.set(false);
+                state.vim_mode.set(VimMode::Normal);
+                reset_reader_state(&state);
+                apply_site_zoom(webview);
+            }
+
+            if event == LoadEvent::Finished {
+                if let Some(uri) = webview.uri() {
+                    record_visit(&uri, &webview.title().unwrap_or_default());
                 }
             }
         }
     });
 
-    let tab_index = notebook.append_page(&webview, Some(&tab_box));
+    connect_zoom_indicator(webview);
 
-    webview.show();
-    tab_label.show();
-    close_button.show();
+    webview.connect_load_failed({
+        let state = state.clone();
+        move |webview, _event, uri, error| {
+            // Leaving the page while it loads is not a failed upgrade
+            let interrupted = error.matches(NetworkError::Cancelled)
+                || error.matches(PolicyError::FrameLoadInterruptedByPolicyChange);
 
-    notebook.set_current_page(Some(tab_index));
-    notebook.set_tab_reorderable(&webview, true);
-    notebook.set_tab_detachable(&webview, true);
-
-    let notebook_webview = notebook.clone();
-    let search_entry_clone = search_entry.clone();
-
-    webview.connect_load_failed(move |webview, _event, uri, error| {
-        if error.message().contains("Name or service not known") {
-            let custom_error_page = format!(
-                r#"
-                        <html>
-                        <head>
-                            <title>404 - {}</title>
-                            <style>
-                                body {{
-                                    background-color: #202020;
-                                    color: white;
-                                    display: flex;
-                                    justify-content: center;
-                                    align-items: center;
-                                    height: 100vh;
-                                    margin: 0;
-                                    font-family: Arial, sans-serif;
-                                    text-align: center;
-                                }}
-                                h1 {{
-                                    font-size: 48px;
-                                }}
-                                p {{
-                                    font-size: 24px;
-                                }}
-                            </style>
-                        </head>
-                        <body>
-                            <div>
-                                <svg xmlns="http://www.w3.org/2000/svg" width="300" height="300" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" class="lucide lucide-cake-slice"><circle cx="9" cy="7" r="2"/><path d="M7.2 7.9 3 11v9c0 .6.4 1 1 1h16c.6 0 1-.4 1-1v-9c0-2-3-6-7-8l-3.6 2.6"/><path d="M16 13H3"/><path d="M16 17H3"/></svg>
-                                <h1>404 Not Found!</h1>
-                                <p>We can't find <strong>{}</strong></p>
-                                <p>but here is some cake to cheer you up</p>
-                            </div>
-                        </body>
-                        </html>
-                        "#,
-                uri, uri
-            );
-            webview.load_html(&custom_error_page, Some(uri));
-            return true;
+            if !interrupted && show_https_fallback(webview, &state, uri, error.message()) {
+                return true;
+            }
+
+            show_error_page(webview, uri, error)
         }
-        false
     });
 
+    let page = page.downgrade();
     webview.connect_context_menu(move |_webview, context_menu, _event, hit_test_result| {
         let menu: ContextMenu = context_menu.clone();
 
@@ -255,13 +431,30 @@ pub fn add_tab(notebook: &Notebook, search_entry: &Entry, uri: Option<&str>) {
 
         let open_link_in_new_tab_act = create_action_with_callback("open-link-in-new-tab", {
             let hit_test_result = hit_test_result.clone();
-
-            let notebook_webview = notebook_webview.clone();
-            let search_entry_clone = search_entry_clone.clone();
+            let page = page.clone();
 
             move |_, _| {
-                if let Some(link_uri) = hit_test_result.link_uri() {
-                    add_tab(&notebook_webview, &search_entry_clone, Some(&link_uri));
+                let page = match page.upgrade() {
+                    Some(page) => page,
+                    None => return,
+                };
+
+                if let (Some(link_uri), Some(browser_window)) =
+                    (hit_test_result.link_uri(), tab_browser_window(&page))
+                {
+                    let new_page = add_tab(
+                        &browser_window.notebook,
+                        &browser_window.search_bar,
+                        Some(&link_uri),
+                    );
+
+                    if let Some(state) = tab_state(&new_page) {
+                        state
+                            .opener
+                            .replace(Some(page.upcast_ref::<Widget>().downgrade()));
+                    }
+
+                    refresh_tab_sidebar(&browser_window.notebook);
                 }
             }
         });
@@ -285,21 +478,395 @@ pub fn add_tab(notebook: &Notebook, search_entry: &Entry, uri: Option<&str>) {
             None,
         );
 
+        let view_source_act = create_action_with_callback("view-source", {
+            let page = page.clone();
+
+            move |_, _| {
+                if let Some(page) = page.upgrade() {
+                    view_page_source(page.upcast_ref());
+                }
+            }
+        });
+
+        let view_source = ContextMenuItem::from_gaction(&view_source_act, "View Page Source", None);
+
         let separator = ContextMenuItem::new_separator();
 
         menu.prepend(&separator);
         menu.prepend(&open_link_in_new_window);
         menu.prepend(&open_link_in_new_tab);
 
+        menu.append(&ContextMenuItem::new_separator());
+        menu.append(&view_source);
+
         false
     });
+}
+
+/// Points a fresh webview at where another one was, history included.
+fn restore_webview(
+    webview: &WebView,
+    session_state: Option<&WebViewSessionState>,
+    uri: Option<&str>,
+) {
+    // Restoring the session state carries the back/forward history over
+    if let Some(state) = session_state {
+        webview.restore_session_state(state);
+    }
+
+    match webview
+        .back_forward_list()
+        .and_then(|list| list.current_item())
+    {
+        Some(item) => webview.go_to_back_forward_list_item(&item),
+        None => match uri {
+            Some(uri) => webview.load_uri(uri),
+            None => webview.load_uri(&new_tab_uri()),
+        },
+    }
+}
+
+/// Closes the tab holding `page`. Pinned tabs have to be unpinned first.
+pub fn close_tab(page: &impl IsA<Widget>) {
+    if is_tab_pinned(page) {
+        return;
+    }
+
+    if let Some(notebook) = page_notebook(page) {
+        if let Some(index) = notebook.page_num(page) {
+            notebook.remove_page(Some(index));
+        }
+    }
+
+    // Destroying the page takes its webview and web process state with it
+    unsafe { page.as_ref().destroy() };
+}
+
+pub fn reload_tab(page: &impl IsA<Widget>) {
+    match page_webview(page) {
+        Some(webview) => webview.reload(),
+        None => resume_tab(page),
+    }
+}
+
+pub fn set_tab_pinned(page: &impl IsA<Widget>, pinned: bool) {
+    let state = match tab_state(page) {
+        Some(state) => state,
+        None => return,
+    };
+
+    state.pinned.set(pinned);
+    state.label.set_visible(!pinned);
+    state.close_button.set_visible(!pinned);
+    state
+        .tab_box
+        .set_size_request(if pinned { -1 } else { TAB_WIDTH }, 15);
+
+    if let Some(notebook) = page_notebook(page) {
+        // Pinned tabs live at the start of the notebook, so both pinning and
+        // unpinning move the tab to the boundary between the two groups.
+        let boundary = notebook
+            .children()
+            .iter()
+            .filter(|other| *other != page.upcast_ref() && is_tab_pinned(*other))
+            .count() as u32;
+
+        notebook.reorder_child(page, Some(boundary));
+        notebook.set_tab_reorderable(page, !pinned);
+        notebook.set_tab_detachable(page, !pinned);
+    }
+
+    save_session();
+}
+
+/// Marks a tab as just used, resetting its idle time.
+pub fn touch_tab(page: &impl IsA<Widget>) {
+    if let Some(state) = tab_state(page) {
+        state.last_active.set(Instant::now());
+    }
+}
+
+/// Destroys the tab's webview, keeping just enough to bring it back later.
+pub fn suspend_tab(page: &impl IsA<Widget>) {
+    let (state, webview) = match (tab_state(page), page_webview(page)) {
+        (Some(state), Some(webview)) => (state, webview),
+        _ => return,
+    };
+
+    let suspended = SuspendedTab {
+        uri: tab_uri(page),
+        title: tab_title(page),
+        session_state: webview.session_state(),
+    };
+
+    state.suspended.replace(Some(suspended));
+    state.tab_box.set_opacity(SUSPENDED_TAB_OPACITY);
+
+    unsafe { webview.destroy() };
+
+    if let Some(notebook) = page_notebook(page) {
+        refresh_tab_sidebar(&notebook);
+    }
+}
+
+/// Gives a suspended tab a new webview at the page it was showing.
+pub fn resume_tab(page: &impl IsA<Widget>) {
+    let (state, page) = match (tab_state(page), page.dynamic_cast_ref::<Box>()) {
+        (Some(state), Some(page)) => (state, page),
+        _ => return,
+    };
+
+    let suspended = match state.suspended.take() {
+        Some(suspended) => suspended,
+        None => return,
+    };
+
+    let webview = create_webview();
+    restore_webview(
+        &webview,
+        suspended.session_state.as_ref(),
+        suspended.uri.as_deref(),
+    );
+
+    attach_webview(page, &webview);
+    state.tab_box.set_opacity(1.0);
+
+    if let Some(notebook) = page_notebook(page) {
+        refresh_tab_sidebar(&notebook);
+    }
+}
+
+/// Suspends background tabs that have been idle longer than the configured
+/// time. Pinned tabs, tabs playing audio and tabs with unsaved form input stay.
+pub fn suspend_idle_tabs() {
+    let minutes = Settings::load().tab_suspend_minutes;
+
+    if minutes == 0 {
+        return;
+    }
+
+    let idle_limit = Duration::from_secs(u64::from(minutes) * 60);
+
+    for browser_window in browser_windows() {
+        let notebook = &browser_window.notebook;
+        let current_page = notebook.current_page();
+
+        for (index, page) in notebook.children().iter().enumerate() {
+            if current_page == Some(index as u32) {
+                touch_tab(page);
+                continue;
+            }
+
+            let state = match tab_state(page) {
+                Some(state) => state,
+                None => continue,
+            };
+
+            let playing_audio =
+                page_webview(page).is_some_and(|webview| webview.is_playing_audio());
+
+            if state.pinned.get()
+                || state.form_dirty.get()
+                || playing_audio
+                || state.last_active.get().elapsed() < idle_limit
+            {
+                continue;
+            }
+
+            suspend_tab(page);
+        }
+    }
+}
+
+pub fn duplicate_tab(page: &impl IsA<Widget>) {
+    let browser_window = match tab_browser_window(page) {
+        Some(browser_window) => browser_window,
+        None => return,
+    };
+
+    let session_state = match page_webview(page) {
+        Some(webview) => webview.session_state(),
+        None => tab_state(page).and_then(|state| {
+            state
+                .suspended
+                .borrow()
+                .as_ref()
+                .and_then(|suspended| suspended.session_state.clone())
+        }),
+    };
+
+    let duplicate = create_webview();
+    restore_webview(&duplicate, session_state.as_ref(), tab_uri(page).as_deref());
+
+    let duplicate_page = append_tab(
+        &browser_window.notebook,
+        &browser_window.search_bar,
+        &duplicate,
+    );
+
+    if let Some(index) = browser_window.notebook.page_num(page) {
+        browser_window
+            .notebook
+            .reorder_child(&duplicate_page, Some(index + 1));
+    }
+}
+
+pub fn move_tab_to_new_window(page: &impl IsA<Widget>) {
+    let notebook = match page_notebook(page) {
+        Some(notebook) => notebook,
+        None => return,
+    };
+
+    let tab_label = match notebook.tab_label(page) {
+        Some(tab_label) => tab_label,
+        None => return,
+    };
+
+    let pinned = is_tab_pinned(page);
+
+    notebook.remove(page);
+
+    let browser_window = create_empty_window();
+    let index = browser_window.notebook.append_page(page, Some(&tab_label));
+
+    browser_window.notebook.set_current_page(Some(index));
+
+    if pinned {
+        set_tab_pinned(page, true);
+    }
+
+    close_window_if_empty(&notebook);
+}
+
+/// Closes the window of `notebook` once its last tab was moved elsewhere.
+pub fn close_window_if_empty(notebook: &Notebook) {
+    if notebook.n_pages() > 0 {
+        return;
+    }
+
+    if let Some(browser_window) = find_browser_window(notebook) {
+        browser_window.window.close();
+    }
+}
+
+pub fn close_other_tabs(page: &impl IsA<Widget>) {
+    if let Some(notebook) = page_notebook(page) {
+        for other in notebook.children() {
+            if &other != page.upcast_ref() {
+                close_tab(&other);
+            }
+        }
+    }
+}
+
+pub fn close_tabs_to_the_right(page: &impl IsA<Widget>) {
+    if let Some(notebook) = page_notebook(page) {
+        if let Some(index) = notebook.page_num(page) {
+            for other in notebook.children().iter().skip(index as usize + 1) {
+                close_tab(other);
+            }
+        }
+    }
+}
+
+pub fn bookmark_all_tabs(notebook: &Notebook) {
+    let mut bookmarks = Bookmarks::load();
+
+    for page in notebook.children() {
+        if let Some(uri) = tab_uri(&page).filter(|uri| uri != NEWTAB_URI) {
+            bookmarks.add(&tab_title(&page), &uri);
+        }
+    }
+
+    bookmarks.save();
+}
+
+pub fn show_tab_menu(page: &Box, event: &gtk::gdk::EventButton) {
+    let menu = gtk::Menu::new();
+
+    let pinned = is_tab_pinned(page);
+
+    append_menu_item(&menu, "Reload", {
+        let page = page.clone();
+        move || reload_tab(&page)
+    });
 
-    let notebook = notebook.clone();
-    close_button.connect_clicked(move |_| {
-        notebook.remove_page(Some(tab_index));
+    append_menu_item(&menu, "Duplicate", {
+        let page = page.clone();
+        move || duplicate_tab(&page)
     });
 
-    search_entry.set_is_focus(true);
+    append_menu_item(&menu, if pinned { "Unpin tab" } else { "Pin tab" }, {
+        let page = page.clone();
+        move || set_tab_pinned(&page, !pinned)
+    });
+
+    if let Some(webview) = page_webview(page) {
+        let muted = webview.is_muted();
+
+        append_menu_item(&menu, if muted { "Unmute tab" } else { "Mute tab" }, {
+            move || webview.set_is_muted(!muted)
+        });
+    }
+
+    menu.append(&gtk::SeparatorMenuItem::new());
+
+    append_group_menu_items(&menu, page);
+
+    menu.append(&gtk::SeparatorMenuItem::new());
+
+    append_menu_item(&menu, "Move to new window", {
+        let page = page.clone();
+        move || move_tab_to_new_window(&page)
+    });
+
+    append_menu_item(&menu, "Bookmark all tabs", {
+        let page = page.clone();
+        move || {
+            if let Some(notebook) = page_notebook(&page) {
+                bookmark_all_tabs(&notebook);
+            }
+        }
+    });
+
+    menu.append(&gtk::SeparatorMenuItem::new());
+
+    append_menu_item(&menu, "Close", {
+        let page = page.clone();
+        move || close_tab(&page)
+    });
+
+    append_menu_item(&menu, "Close other tabs", {
+        let page = page.clone();
+        move || close_other_tabs(&page)
+    });
+
+    append_menu_item(&menu, "Close tabs to the right", {
+        let page = page.clone();
+        move || close_tabs_to_the_right(&page)
+    });
+
+    menu.show_all();
+    menu.popup_at_pointer(Some(event));
+}
+
+fn append_menu_item<F>(menu: &gtk::Menu, label: &str, callback: F)
+where
+    F: Fn() + 'static,
+{
+    let item = gtk::MenuItem::with_label(label);
+
+    item.connect_activate(move |_| callback());
+
+    menu.append(&item);
+}
+
+fn favicon_pixbuf(surface: &gtk::cairo::Surface) -> Option<Pixbuf> {
+    let surface = gtk::cairo::ImageSurface::try_from(surface.clone()).ok()?;
+    let pixbuf =
+        gtk::gdk::pixbuf_get_from_surface(&surface, 0, 0, surface.width(), surface.height())?;
+
+    pixbuf.scale_simple(FAVICON_SIZE, FAVICON_SIZE, InterpType::Bilinear)
 }
 
 fn create_action_with_callback<F>(name: &str, callback: F) -> SimpleAction
@@ -0,0 +1,379 @@
+use gtk::gio::{TlsCertificate, TlsCertificateFlags};
+use gtk::glib::ChecksumType;
+use gtk::prelude::*;
+use gtk::Box;
+use javascriptcore::ValueExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::rc::Rc;
+use url::Url;
+use webkit2gtk::{
+    LoadEvent, UserContentManagerExt, WebContext, WebContextExt, WebView, WebViewExt,
+};
+
+use crate::https::show_https_fallback;
+use crate::reader::escape_html;
+use crate::settings::{create_settings_list, SettingsListRow};
+use crate::storage::{load_json, save_json};
+use crate::tabs::TabState;
+
+/// Script message the certificate error page uses to add an exception
+pub const TLS_MESSAGE: &str = "abrwTls";
+
+/// Certificates the user chose to trust anyway, as PEM by host.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TlsExceptions {
+    pub hosts: HashMap<String, String>,
+}
+
+impl TlsExceptions {
+    pub fn save(&self) {
+        save_json("tls_exceptions.json", self);
+    }
+
+    pub fn load() -> TlsExceptions {
+        load_json("tls_exceptions.json")
+    }
+}
+
+/// A certificate error the tab is showing the error page for.
+pub struct PendingTlsError {
+    pub uri: String,
+    pub host: String,
+    pub certificate: TlsCertificate,
+    /// Random value only the error page knows, which it sends to accept
+    pub token: String,
+}
+
+/// Tells `context` about the saved exceptions. WebKit forgets them on exit.
+pub fn apply_tls_exceptions(context: &WebContext) {
+    for (host, pem) in TlsExceptions::load().hosts {
+        match TlsCertificate::from_pem(&pem) {
+            Ok(certificate) => context.allow_tls_certificate_for_host(&certificate, &host),
+            Err(err) => println!("Invalid saved certificate for {}: {}", host, err),
+        }
+    }
+}
+
+fn add_tls_exception(context: &WebContext, host: &str, certificate: &TlsCertificate) {
+    context.allow_tls_certificate_for_host(certificate, host);
+
+    if let Some(pem) = certificate.certificate_pem() {
+        let mut exceptions = TlsExceptions::load();
+        exceptions.hosts.insert(host.to_string(), pem.to_string());
+        exceptions.save();
+    }
+}
+
+pub fn certificate_error_descriptions(errors: TlsCertificateFlags) -> Vec<&'static str> {
+    let descriptions = [
+        (
+            TlsCertificateFlags::UNKNOWN_CA,
+            "The certificate is not signed by a trusted authority. It may be self-signed.",
+        ),
+        (
+            TlsCertificateFlags::BAD_IDENTITY,
+            "The certificate belongs to a different site.",
+        ),
+        (
+            TlsCertificateFlags::NOT_ACTIVATED,
+            "The certificate is not valid yet.",
+        ),
+        (TlsCertificateFlags::EXPIRED, "The certificate has expired."),
+        (
+            TlsCertificateFlags::REVOKED,
+            "The certificate has been revoked.",
+        ),
+        (
+            TlsCertificateFlags::INSECURE,
+            "The certificate uses insecure cryptography.",
+        ),
+        (
+            TlsCertificateFlags::GENERIC_ERROR,
+            "The certificate could not be verified.",
+        ),
+    ];
+
+    descriptions
+        .iter()
+        .filter(|(flag, _)| errors.contains(*flag))
+        .map(|(_, description)| *description)
+        .collect()
+}
+
+/// SHA-256 fingerprint of the certificate, as colon separated hex.
+pub fn certificate_fingerprint(certificate: &TlsCertificate) -> String {
+    let der = match certificate.certificate() {
+        Some(der) => der,
+        None => return String::new(),
+    };
+
+    let checksum = gtk::glib::compute_checksum_for_data(ChecksumType::Sha256, &der)
+        .map(|checksum| checksum.to_uppercase())
+        .unwrap_or_default();
+
+    checksum
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| String::from_utf8_lossy(pair).to_string())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Subject, issuer, validity and fingerprint as (label, value) pairs.
+pub fn certificate_details(certificate: &TlsCertificate) -> Vec<(&'static str, String)> {
+    let format_date = |date: Option<gtk::glib::DateTime>| {
+        date.and_then(|date| date.format("%Y-%m-%d %H:%M").ok())
+            .map(|date| date.to_string())
+            .unwrap_or_default()
+    };
+
+    vec![
+        (
+            "Subject",
+            certificate
+                .subject_name()
+                .map(|name| name.to_string())
+                .unwrap_or_default(),
+        ),
+        (
+            "Issuer",
+            certificate
+                .issuer_name()
+                .map(|name| name.to_string())
+                .unwrap_or_default(),
+        ),
+        ("Valid from", format_date(certificate.not_valid_before())),
+        ("Valid until", format_date(certificate.not_valid_after())),
+        ("SHA-256", certificate_fingerprint(certificate)),
+    ]
+}
+
+fn tls_error_page_html(
+    uri: &str,
+    host: &str,
+    certificate: &TlsCertificate,
+    errors: TlsCertificateFlags,
+    token: &str,
+) -> String {
+    let problems: String = certificate_error_descriptions(errors)
+        .iter()
+        .map(|description| format!("<li>{}</li>", description))
+        .collect();
+
+    let details: String = certificate_details(certificate)
+        .iter()
+        .map(|(label, value)| {
+            format!(
+                "<tr><td class=\"darkened\">{}</td><td>{}</td></tr>",
+                label,
+                escape_html(value)
+            )
+        })
+        .collect();
+
+    format!(
+        r##"
+    <!DOCTYPE html>
+    <html lang="en">
+    <head>
+        <meta charset="UTF-8">
+        <title>Certificate error</title>
+        <style>
+            body, html {{
+                margin: 0;
+                background-color: #202020;
+                color: #f1f1f1;
+                font-family: "Hack", sans-serif;
+            }}
+            .container {{
+                max-width: 700px;
+                margin: 0 auto;
+                padding: 60px 20px;
+            }}
+            .darkened {{
+                color: #888;
+            }}
+            td {{
+                padding: 4px 15px 4px 0;
+                vertical-align: top;
+                overflow-wrap: anywhere;
+            }}
+            button {{
+                margin: 10px 10px 0 0;
+                background: #2a2a2a;
+                color: #f1f1f1;
+                border: none;
+                border-radius: 7px;
+                padding: 8px 14px;
+                cursor: pointer;
+            }}
+            button:hover {{
+                background: #333;
+            }}
+            details {{
+                margin-top: 20px;
+            }}
+        </style>
+    </head>
+    <body>
+        <div class="container">
+            <h1>This connection is not private</h1>
+            <p>The certificate of <strong>{host}</strong> could not be trusted. Someone may be impersonating the site.</p>
+            <ul>{problems}</ul>
+            <p class="darkened">{uri}</p>
+            <table>{details}</table>
+            <button onclick="history.back()">Go back</button>
+            <details>
+                <summary class="darkened">Advanced</summary>
+                <p>Only continue if you know why this certificate is not trusted, like on your own development server. The exception can be revoked in settings.</p>
+                <button onclick="window.webkit.messageHandlers.abrwTls.postMessage('{token}')">Trust this certificate for {host}</button>
+            </details>
+        </div>
+    </body>
+    </html>
+    "##,
+        host = escape_html(host),
+        problems = problems,
+        uri = escape_html(uri),
+        details = details,
+        token = token,
+    )
+}
+
+pub fn add_tls_message_handler(webview: &WebView) {
+    if let Some(content_manager) = webview.user_content_manager() {
+        content_manager.register_script_message_handler(TLS_MESSAGE);
+    }
+}
+
+/// Shows the certificate error page for TLS failures in `webview` and adds
+/// the exception when the user asks for it there.
+pub fn connect_tls_errors(webview: &WebView, state: Rc<TabState>) {
+    webview.connect_load_failed_with_tls_errors({
+        let state = state.clone();
+        move |webview, uri, certificate, errors| {
+            let host = match Url::parse(uri)
+                .ok()
+                .and_then(|url| url.host_str().map(|host| host.to_string()))
+            {
+                Some(host) => host,
+                None => return false,
+            };
+
+            println!("Certificate error for {}: {:?}", host, errors);
+
+            // Sites only reached through an HTTPS-only upgrade offer HTTP instead
+            if show_https_fallback(
+                webview,
+                &state,
+                uri,
+                &certificate_error_descriptions(errors).join(" "),
+            ) {
+                return true;
+            }
+
+            let token = gtk::glib::uuid_string_random().to_string();
+
+            webview.load_alternate_html(
+                &tls_error_page_html(uri, &host, certificate, errors, &token),
+                uri,
+                Some(uri),
+            );
+
+            state.tls_error.replace(Some(PendingTlsError {
+                uri: uri.to_string(),
+                host,
+                certificate: certificate.clone(),
+                token,
+            }));
+
+            true
+        }
+    });
+
+    webview.connect_load_changed({
+        let state = state.clone();
+        move |webview, event| {
+            if event != LoadEvent::Committed {
+                return;
+            }
+
+            // The error page itself commits at the failed uri
+            let left_error_page = state
+                .tls_error
+                .borrow()
+                .as_ref()
+                .is_some_and(|pending| webview.uri().as_deref() != Some(pending.uri.as_str()));
+
+            if left_error_page {
+                state.tls_error.replace(None);
+            }
+        }
+    });
+
+    // Only the error page for the failed host may add the exception. Any page
+    // could post to the handler, and webview.uri() already reports the next
+    // page while the previous one still runs, so the page proves itself with
+    // its token
+    let content_manager = match webview.user_content_manager() {
+        Some(content_manager) => content_manager,
+        None => return,
+    };
+
+    let webview = webview.downgrade();
+
+    content_manager.connect_script_message_received(Some(TLS_MESSAGE), move |_, result| {
+        let webview = match webview.upgrade() {
+            Some(webview) => webview,
+            None => return,
+        };
+
+        let token = match result.js_value() {
+            Some(value) => value.to_str().to_string(),
+            None => return,
+        };
+
+        let is_error_page = state.tls_error.borrow().as_ref().is_some_and(|pending| {
+            pending.token == token && webview.uri().as_deref() == Some(pending.uri.as_str())
+        });
+
+        if !is_error_page {
+            return;
+        }
+
+        if let (Some(pending), Some(context)) = (state.tls_error.take(), webview.context()) {
+            add_tls_exception(&context, &pending.host, &pending.certificate);
+            webview.load_uri(&pending.uri);
+        }
+    });
+}
+
+/// Lists the hosts with certificate exceptions in the settings window.
+pub fn create_tls_exceptions_section(parent_box: &Box) {
+    let mut hosts: Vec<String> = TlsExceptions::load().hosts.into_keys().collect();
+    hosts.sort();
+
+    let rows = hosts
+        .into_iter()
+        .map(|host| SettingsListRow {
+            label: host.clone(),
+            detail: None,
+            key: host,
+        })
+        .collect();
+
+    create_settings_list(
+        parent_box,
+        "Certificate exceptions",
+        "No exceptions",
+        ("Revoke", Some("Takes effect after restarting abrw")),
+        rows,
+        |host| {
+            // WebKit cannot take an allowed certificate back while running
+            let mut exceptions = TlsExceptions::load();
+            exceptions.hosts.remove(host);
+            exceptions.save();
+        },
+    );
+}
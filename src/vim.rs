@@ -0,0 +1,388 @@
+use gtk::gdk::keys::{constants, Key};
+use gtk::gdk::ModifierType;
+use gtk::prelude::*;
+use gtk::{Entry, Notebook};
+use javascriptcore::ValueExt;
+use std::cell::Cell;
+use std::rc::Rc;
+use url::Url;
+use webkit2gtk::{
+    UserContentInjectedFrames, UserContentManagerExt, UserScript, UserScriptInjectionTime, WebView,
+    WebViewExt,
+};
+
+use crate::connections::get_webview;
+use crate::keybindings::{run_action, Action};
+use crate::settings::Settings;
+use crate::tabs::{add_tab, tab_browser_window, tab_state, TabState};
+use crate::BrowserWindow;
+
+/// Script message the vim script uses to report mode changes and links to open
+pub const VIM_MESSAGE: &str = "abrwVim";
+
+const SCROLL_STEP: i32 = 60;
+
+const VIM_SCRIPT: &str = r#"
+(function () {
+    const post = (message) =>
+        window.webkit.messageHandlers.abrwVim.postMessage(JSON.stringify(message));
+
+    const isEditable = (element) =>
+        element instanceof Element &&
+        (element.isContentEditable ||
+            element.matches("textarea, select, input:not([type=button]):not([type=submit]):not([type=reset]):not([type=checkbox]):not([type=radio]):not([type=image])"));
+
+    document.addEventListener("focusin", (event) => {
+        if (isEditable(event.target)) post({ mode: "insert" });
+    }, true);
+
+    document.addEventListener("focusout", (event) => {
+        if (isEditable(event.target)) post({ mode: "normal" });
+    }, true);
+
+    // Fields focused before the listeners were added, like autofocus ones
+    // in frames, don't send focusin anymore
+    const checkFocus = () => {
+        if (isEditable(document.activeElement)) post({ mode: "insert" });
+    };
+
+    checkFocus();
+    document.addEventListener("DOMContentLoaded", checkFocus);
+
+    const HINT_CHARS = "asdfghjkl";
+    const CLICKABLE = "a[href], button, input, select, textarea, summary, [onclick], [role=button], [role=link], [contenteditable]";
+
+    let hints = null;
+
+    function hintLabels(count) {
+        let length = 1;
+        while (Math.pow(HINT_CHARS.length, length) < count) length++;
+
+        const labels = [];
+        for (let i = 0; i < count; i++) {
+            let label = "";
+            let n = i;
+            for (let j = 0; j < length; j++) {
+                label = HINT_CHARS[n % HINT_CHARS.length] + label;
+                n = Math.floor(n / HINT_CHARS.length);
+            }
+            labels.push(label);
+        }
+        return labels;
+    }
+
+    function clearHints() {
+        if (!hints) return;
+        hints.container.remove();
+        document.removeEventListener("keydown", onHintKey, true);
+        hints = null;
+        post({ mode: "normal" });
+    }
+
+    function follow(element, newTab) {
+        // Sent while still in hint mode, links are only opened from it
+        if (newTab && element.href) post({ open: element.href });
+
+        clearHints();
+
+        if (newTab && element.href) {
+            return;
+        } else if (isEditable(element)) {
+            element.focus();
+        } else {
+            element.click();
+        }
+    }
+
+    function onHintKey(event) {
+        event.preventDefault();
+        event.stopPropagation();
+
+        if (event.key === "Escape") {
+            clearHints();
+            return;
+        }
+
+        if (event.key === "Backspace") {
+            hints.typed = hints.typed.slice(0, -1);
+        } else if (event.key.length === 1) {
+            hints.typed += event.key.toLowerCase();
+        }
+
+        const matching = hints.items.filter((item) => item.label.startsWith(hints.typed));
+
+        if (matching.length === 0) {
+            clearHints();
+            return;
+        }
+
+        if (matching.length === 1 && matching[0].label === hints.typed) {
+            follow(matching[0].element, hints.newTab);
+            return;
+        }
+
+        for (const item of hints.items) {
+            item.marker.style.display = item.label.startsWith(hints.typed) ? "" : "none";
+        }
+    }
+
+    window.abrwVimHints = function (newTab) {
+        clearHints();
+
+        const elements = [...document.querySelectorAll(CLICKABLE)].filter((element) => {
+            const rect = element.getBoundingClientRect();
+            return rect.width > 0 && rect.height > 0 &&
+                rect.bottom > 0 && rect.right > 0 &&
+                rect.top < window.innerHeight && rect.left < window.innerWidth;
+        });
+
+        if (elements.length === 0) return;
+
+        const container = document.createElement("div");
+        container.style.cssText = "position: fixed; top: 0; left: 0; z-index: 2147483647; pointer-events: none;";
+
+        const labels = hintLabels(elements.length);
+        const items = elements.map((element, i) => {
+            const rect = element.getBoundingClientRect();
+            const marker = document.createElement("span");
+            marker.textContent = labels[i];
+            marker.style.cssText =
+                "position: fixed; padding: 0 3px; font: bold 11px monospace; text-transform: uppercase;" +
+                "background: #ebcb8b; color: #202020; border: 1px solid #202020; border-radius: 3px;" +
+                `top: ${Math.max(rect.top, 0)}px; left: ${Math.max(rect.left, 0)}px;`;
+            container.appendChild(marker);
+            return { element, marker, label: labels[i] };
+        });
+
+        document.documentElement.appendChild(container);
+        document.addEventListener("keydown", onHintKey, true);
+
+        hints = { container, items, newTab, typed: "" };
+        post({ mode: "hints" });
+    };
+})();
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VimMode {
+    Normal,
+    Insert,
+    Hints,
+}
+
+/// What the address bar does with its text while it is used as a vim prompt
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Prompt {
+    Open,
+    TabOpen,
+}
+
+thread_local! {
+    static PROMPT: Cell<Option<Prompt>> = const { Cell::new(None) };
+    // First half of a two key command like "gg"
+    static PENDING_G: Cell<bool> = const { Cell::new(false) };
+    // Settings::vim_mode, read once instead of on every key press
+    static ENABLED: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+fn vim_enabled() -> bool {
+    ENABLED.with(|enabled| match enabled.get() {
+        Some(value) => value,
+        None => {
+            let value = Settings::load().vim_mode;
+            enabled.set(Some(value));
+            value
+        }
+    })
+}
+
+/// Updates the cached setting after vim mode was turned on or off.
+pub fn set_vim_enabled(value: bool) {
+    ENABLED.with(|enabled| enabled.set(Some(value)));
+}
+
+pub fn take_prompt() -> Option<Prompt> {
+    PROMPT.with(|prompt| prompt.take())
+}
+
+/// Added to every webview, so turning vim mode on works in open tabs too.
+/// The script only reports focus; keys are handled by `handle_key`.
+pub fn add_vim_script(webview: &WebView) {
+    if let Some(content_manager) = webview.user_content_manager() {
+        content_manager.register_script_message_handler(VIM_MESSAGE);
+        content_manager.add_script(&UserScript::new(
+            VIM_SCRIPT,
+            UserContentInjectedFrames::AllFrames,
+            UserScriptInjectionTime::Start,
+            &[],
+            &[],
+        ));
+    }
+}
+
+/// Keeps the tab's mode in sync with the page and opens links picked with `F`.
+pub fn connect_vim_messages(webview: &WebView, state: Rc<TabState>) {
+    if let Some(content_manager) = webview.user_content_manager() {
+        let webview = webview.downgrade();
+
+        content_manager.connect_script_message_received(Some(VIM_MESSAGE), move |_, result| {
+            let message: serde_json::Value = match result.js_value() {
+                Some(value) => {
+                    serde_json::from_str(&value.to_str()).unwrap_or(serde_json::Value::Null)
+                }
+                None => return,
+            };
+
+            if let Some(uri) = message["open"].as_str() {
+                // Any page can post this, so only the link picked in hint
+                // mode is opened, and only once
+                let picked = vim_enabled() && state.vim_mode.get() == VimMode::Hints;
+                state.vim_mode.set(VimMode::Normal);
+
+                let is_web = Url::parse(uri)
+                    .map(|url| matches!(url.scheme(), "http" | "https"))
+                    .unwrap_or(false);
+
+                if !picked || !is_web {
+                    return;
+                }
+
+                if let Some(browser_window) = webview
+                    .upgrade()
+                    .and_then(|webview| webview.parent())
+                    .and_then(|page| tab_browser_window(&page))
+                {
+                    add_tab(
+                        &browser_window.notebook,
+                        &browser_window.search_bar,
+                        Some(uri),
+                    );
+                }
+
+                return;
+            }
+
+            match message["mode"].as_str() {
+                Some("insert") => state.vim_mode.set(VimMode::Insert),
+                Some("hints") => state.vim_mode.set(VimMode::Hints),
+                Some("normal") => state.vim_mode.set(VimMode::Normal),
+                _ => {}
+            }
+        });
+    }
+}
+
+/// Handles a key press in normal mode. Returns false when the key should
+/// reach the page as usual.
+pub fn handle_key(browser_window: &BrowserWindow, event: &gtk::gdk::EventKey) -> bool {
+    let modifiers = event.state()
+        & (ModifierType::CONTROL_MASK | ModifierType::MOD1_MASK | ModifierType::SUPER_MASK);
+
+    if !modifiers.is_empty() {
+        return false;
+    }
+
+    // Only keys aimed at the page, the address bar and dialogs keep theirs
+    let webview = match get_webview(&browser_window.notebook) {
+        Some(webview) => webview,
+        None => return false,
+    };
+
+    if !webview.has_focus() || !vim_enabled() {
+        return false;
+    }
+
+    let state = match webview.parent().and_then(|page| tab_state(&page)) {
+        Some(state) => state,
+        None => return false,
+    };
+
+    match state.vim_mode.get() {
+        VimMode::Insert => {
+            if event.keyval() == constants::Escape {
+                webview.run_javascript(
+                    "document.activeElement && document.activeElement.blur();",
+                    gtk::gio::Cancellable::NONE,
+                    |_| {},
+                );
+                state.vim_mode.set(VimMode::Normal);
+                return true;
+            }
+
+            false
+        }
+        // The hint script reads keys itself
+        VimMode::Hints => false,
+        VimMode::Normal => handle_normal_key(browser_window, &webview, event.keyval()),
+    }
+}
+
+fn handle_normal_key(browser_window: &BrowserWindow, webview: &WebView, key: Key) -> bool {
+    let pending_g = PENDING_G.with(|pending| pending.replace(false));
+
+    match key {
+        constants::g if pending_g => run_script(webview, "window.scrollTo(0, 0);"),
+        constants::g => PENDING_G.with(|pending| pending.set(true)),
+        constants::G => run_script(
+            webview,
+            "window.scrollTo(0, document.documentElement.scrollHeight);",
+        ),
+        constants::j => run_script(webview, &format!("window.scrollBy(0, {});", SCROLL_STEP)),
+        constants::k => run_script(webview, &format!("window.scrollBy(0, -{});", SCROLL_STEP)),
+        constants::H => run_action(Action::GoBack, browser_window),
+        constants::L => run_action(Action::GoForward, browser_window),
+        constants::J => run_action(Action::PreviousTab, browser_window),
+        constants::K => run_action(Action::NextTab, browser_window),
+        constants::o => open_prompt(browser_window, Prompt::Open),
+        constants::O => open_prompt(browser_window, Prompt::TabOpen),
+        constants::slash => run_action(Action::Find, browser_window),
+        constants::n => run_action(Action::FindNext, browser_window),
+        constants::N => run_action(Action::FindPrevious, browser_window),
+        constants::f => run_script(webview, "window.abrwVimHints(false);"),
+        constants::F => run_script(webview, "window.abrwVimHints(true);"),
+        constants::Escape => {}
+        _ => return false,
+    }
+
+    true
+}
+
+fn run_script(webview: &WebView, script: &str) {
+    webview.run_javascript(script, gtk::gio::Cancellable::NONE, |_| {});
+}
+
+fn open_prompt(browser_window: &BrowserWindow, prompt: Prompt) {
+    let search_bar = &browser_window.search_bar;
+
+    PROMPT.with(|current| current.set(Some(prompt)));
+
+    search_bar.set_placeholder_text(Some(match prompt {
+        Prompt::Open => "Open",
+        Prompt::TabOpen => "Open in new tab",
+    }));
+    search_bar.set_text("");
+    search_bar.grab_focus();
+}
+
+/// Drops a prompt that was left without activating it.
+pub fn connect_prompt_focus_out(search_bar: &Entry, notebook: &Notebook) {
+    search_bar.connect_focus_out_event({
+        let notebook = notebook.clone();
+        move |search_bar, _| {
+            if take_prompt().is_some() {
+                close_prompt(search_bar, &notebook);
+            }
+
+            gtk::glib::Propagation::Proceed
+        }
+    });
+}
+
+/// Puts the address bar back to showing the current page.
+pub fn close_prompt(search_bar: &Entry, notebook: &Notebook) {
+    search_bar.set_placeholder_text(None);
+
+    if let Some(webview) = get_webview(notebook) {
+        search_bar.set_text(&webview.uri().unwrap_or_default());
+    }
+}
@@ -5,6 +5,7 @@ use gtk::glib::gobject_ffi::GObject;
 use gtk::glib::{ffi::GError, translate::ToGlibPtr};
 use gtk::prelude::{FileChooserExt, FileExt, NativeDialogExt};
 use gtk::{FileChooserAction, FileChooserNative, ResponseType, Window};
+use serde::{Deserialize, Serialize};
 use std::env;
 use std::ffi::{c_void, CStr, CString};
 use std::path::Path;
@@ -14,9 +15,11 @@ use std::{
     io::{BufReader, Read},
 };
 use tokio::runtime::Runtime;
+use url::Url;
 use webkit2gtk::{
-    CookieManagerExt, Download, DownloadExt, SettingsExt, WebContext, WebContextExt, WebView,
-    WebViewExt,
+    CookieManagerExt, Download, DownloadExt, SettingsExt, UserContentInjectedFrames,
+    UserContentManagerExt, UserScript, UserScriptInjectionTime, WebContext, WebContextExt, WebView,
+    WebViewExt, WebsiteData, WebsiteDataManagerExt, WebsiteDataManagerExtManual, WebsiteDataTypes,
 };
 use webkit2gtk_sys::{
     webkit_settings_get_enable_javascript, webkit_settings_get_enable_webgl,
@@ -27,18 +30,68 @@ use webkit2gtk_sys::{
     WebKitUserContentFilterStore, WebKitUserContentManager,
 };
 
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum WebviewSetting {
     Javascript,
     WebGL,
     JsClipboardAccess,
+    Autoplay,
+    Images,
+}
+
+impl WebviewSetting {
+    pub const ALL: [WebviewSetting; 5] = [
+        WebviewSetting::Javascript,
+        WebviewSetting::WebGL,
+        WebviewSetting::JsClipboardAccess,
+        WebviewSetting::Autoplay,
+        WebviewSetting::Images,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            WebviewSetting::Javascript => "Javascript enabled",
+            WebviewSetting::WebGL => "WebGL enabled",
+            WebviewSetting::JsClipboardAccess => "Js Can access clipboard",
+            WebviewSetting::Autoplay => "Autoplay media",
+            WebviewSetting::Images => "Load images",
+        }
+    }
+
+    /// The value from settings, used on sites without an override.
+    pub fn default_value(&self, settings: &Settings) -> bool {
+        match self {
+            WebviewSetting::Javascript => settings.enable_javascript,
+            WebviewSetting::WebGL => settings.enable_webgl,
+            WebviewSetting::JsClipboardAccess => settings.javascript_can_access_clipboard,
+            WebviewSetting::Autoplay => !settings.media_playback_requires_user_gesture,
+            WebviewSetting::Images => true,
+        }
+    }
 }
 
 use std::error::Error;
 
 const BLOCK_LIST_IDENT: *const i8 = "blocklist\0".as_ptr() as *const i8;
 
+/// Script message sent whenever the user types into a form field
+pub const FORM_INPUT_MESSAGE: &str = "abrwFormInput";
+
+const FORM_INPUT_SCRIPT: &str = r#"
+    document.addEventListener("input", function (event) {
+        if (event.target.matches("input, textarea, select, [contenteditable]")) {
+            window.webkit.messageHandlers.abrwFormInput.postMessage(true);
+        }
+    }, true);
+"#;
+
+use crate::downloads::track_download;
+use crate::https::add_https_message_handler;
+use crate::internal::{add_internal_message_handler, register_internal_scheme};
+use crate::reader::add_reader_detection;
 use crate::settings::Settings;
+use crate::tls::{add_tls_message_handler, apply_tls_exceptions};
+use crate::vim::add_vim_script;
 
 static mut SHARED_CONTEXT: Option<WebContext> = None;
 
@@ -62,61 +115,75 @@ pub fn create_webview() -> WebView {
                 webkit2gtk::CookiePersistentStorage::Sqlite,
             );
 
+            context.set_favicon_database_directory(None);
+
+            context.connect_download_started(move |_, download| {
+                let download: Download = download.clone();
+                track_download(&download);
+
+                let file_chooser = FileChooserNative::new(
+                    Some("Save File"),
+                    Some(&Window::new(gtk::WindowType::Popup)),
+                    FileChooserAction::Save,
+                    None,
+                    None,
+                );
+
+                let home_dir = env::var("HOME").unwrap_or_else(|_| String::from("/"));
+                let downloads_path = Path::new(&home_dir).join("Downloads");
+
+                file_chooser.set_current_folder(&downloads_path);
+                file_chooser.run();
+
+                file_chooser.set_do_overwrite_confirmation(true);
+
+                download.connect_estimated_progress_notify(move |download| {
+                    let current_progress = download.estimated_progress();
+                    println!("{}", current_progress)
+                });
+
+                file_chooser.connect_response(move |dialog, res| {
+                    if res == ResponseType::Accept {
+                        if let Some(file) = dialog.file() {
+                            if let Some(file_path) = file.path() {
+                                download
+                                    .set_destination(&format!("file://{}", file_path.display()));
+                                println!("{}", file_path.display());
+                            }
+                        }
+                    } else {
+                        download.cancel();
+                    }
+                    dialog.destroy();
+                });
+
+                file_chooser.show();
+            });
+
+            register_internal_scheme(&context);
+            apply_tls_exceptions(&context);
+
             context
         })
     };
 
     context.set_cache_model(webkit2gtk::CacheModel::DocumentViewer);
 
-    context.connect_download_started(move |_, download| {
-        let download: Download = download.clone();
-
-        let file_chooser = FileChooserNative::new(
-            Some("Save File"),
-            Some(&Window::new(gtk::WindowType::Popup)),
-            FileChooserAction::Save,
-            None,
-            None,
-        );
-
-        let home_dir = env::var("HOME").unwrap_or_else(|_| String::from("/"));
-        let downloads_path = Path::new(&home_dir).join("Downloads");
-
-        file_chooser.set_current_folder(&downloads_path);
-        file_chooser.run();
-
-        file_chooser.set_do_overwrite_confirmation(true);
-
-        download.connect_estimated_progress_notify(move |download| {
-            let current_progress = download.estimated_progress();
-            println!("{}", current_progress)
-        });
-
-        file_chooser.connect_response(move |dialog, res| {
-            if res == ResponseType::Accept {
-                if let Some(file) = dialog.file() {
-                    if let Some(file_path) = file.path() {
-                        download.set_destination(&format!("file://{}", file_path.display()));
-                        println!("{}", file_path.display());
-                    }
-                }
-            } else {
-                download.cancel();
-            }
-            dialog.destroy();
-        });
-
-        file_chooser.show();
-    });
-
     let webview: WebView = WebView::with_context(context);
 
     add_filter(&webview);
+    add_form_input_tracking(&webview);
+    add_reader_detection(&webview);
+    add_internal_message_handler(&webview);
+    add_tls_message_handler(&webview);
+    add_https_message_handler(&webview);
 
     let settings = WebViewExt::settings(&webview).unwrap();
     let settings_json = Settings::load();
 
-    settings.set_enable_developer_extras(true);
+    add_vim_script(&webview);
+
+    settings.set_enable_developer_extras(!settings_json.disable_developer_extras);
     settings.set_enable_smooth_scrolling(false);
     settings.set_enable_plugins(false);
 
@@ -138,6 +205,7 @@ pub fn create_webview() -> WebView {
     settings.set_enable_javascript(settings_json.enable_javascript);
     settings.set_enable_webgl(settings_json.enable_webgl);
     settings.set_enable_page_cache(settings_json.page_cache);
+    settings.set_zoom_text_only(settings_json.zoom_text_only);
     settings.set_media_playback_requires_user_gesture(
         settings_json.media_playback_requires_user_gesture,
     );
@@ -148,6 +216,19 @@ pub fn create_webview() -> WebView {
     return webview;
 }
 
+fn add_form_input_tracking(webview: &WebView) {
+    if let Some(content_manager) = webview.user_content_manager() {
+        content_manager.register_script_message_handler(FORM_INPUT_MESSAGE);
+        content_manager.add_script(&UserScript::new(
+            FORM_INPUT_SCRIPT,
+            UserContentInjectedFrames::AllFrames,
+            UserScriptInjectionTime::Start,
+            &[],
+            &[],
+        ));
+    }
+}
+
 unsafe extern "C" fn filter_save_callback(
     caller: *mut GObject,
     res: *mut GAsyncResult,
@@ -294,6 +375,61 @@ pub fn toggle_content_filter(webview: &WebView, enable_filter: bool) {
     }
 }
 
+/// Removes the cookies of the site loaded in `webview`. WebKit keeps cookies
+/// by domain, so this clears the whole domain the host is part of, like
+/// `example.com` and its subdomains for `www.example.com`.
+pub fn clear_site_cookies(webview: &WebView) {
+    let host = match webview
+        .uri()
+        .and_then(|uri| Url::parse(&uri).ok())
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+    {
+        Some(host) => host,
+        None => return,
+    };
+
+    let data_manager = match webview.website_data_manager() {
+        Some(data_manager) => data_manager,
+        None => return,
+    };
+
+    data_manager.clone().fetch(
+        WebsiteDataTypes::COOKIES,
+        gtk::gio::Cancellable::NONE,
+        move |result| {
+            let website_data = match result {
+                Ok(website_data) => website_data,
+                Err(err) => {
+                    println!("Failed to fetch cookies: {}", err);
+                    return;
+                }
+            };
+
+            // Cookie data is named after the domain it belongs to, which is
+            // the host itself or one of its parents
+            let site_data: Vec<&WebsiteData> = website_data
+                .iter()
+                .filter(|data| {
+                    data.name().is_some_and(|name| {
+                        let name = name.trim_start_matches('.');
+                        host == name || host.ends_with(&format!(".{}", name))
+                    })
+                })
+                .collect();
+
+            data_manager.remove(
+                WebsiteDataTypes::COOKIES,
+                &site_data,
+                gtk::gio::Cancellable::NONE,
+                move |result| match result {
+                    Ok(()) => println!("Cookies cleared for {}", host),
+                    Err(err) => println!("Failed to clear cookies: {}", err),
+                },
+            );
+        },
+    );
+}
+
 pub fn get_webview_setting(webview: &WebView, setting: WebviewSetting) -> Option<bool> {
     let settings = WebViewExt::settings(webview).unwrap();
 
@@ -313,6 +449,8 @@ pub fn get_webview_setting(webview: &WebView, setting: WebviewSetting) -> Option
                 webkit_settings_get_javascript_can_access_clipboard(settings.to_glib_none().0) != 0
             });
         }
+        WebviewSetting::Autoplay => Some(!settings.is_media_playback_requires_user_gesture()),
+        WebviewSetting::Images => Some(settings.is_auto_load_images()),
     }
 }
 
@@ -329,5 +467,11 @@ pub fn change_webview_setting(webview: &WebView, setting: WebviewSetting, value:
         WebviewSetting::JsClipboardAccess => {
             settings.set_javascript_can_access_clipboard(value);
         }
+        WebviewSetting::Autoplay => {
+            settings.set_media_playback_requires_user_gesture(!value);
+        }
+        WebviewSetting::Images => {
+            settings.set_auto_load_images(value);
+        }
     }
 }
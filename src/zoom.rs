@@ -0,0 +1,128 @@
+use gtk::prelude::*;
+use gtk::{Button, Notebook};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use url::Url;
+use webkit2gtk::{SettingsExt, WebView, WebViewExt};
+
+use crate::connections::get_webview;
+use crate::settings::Settings;
+use crate::storage::{load_json, save_json};
+use crate::tabs::{all_webviews, tab_browser_window};
+
+pub const ZOOM_STEP: f64 = 0.1;
+const MIN_ZOOM: f64 = 0.3;
+const MAX_ZOOM: f64 = 5.0;
+
+/// Zoom levels the user picked, by host.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SiteZoom {
+    pub levels: HashMap<String, f64>,
+}
+
+impl SiteZoom {
+    pub fn save(&self) {
+        save_json("zoom.json", self);
+    }
+
+    pub fn load() -> SiteZoom {
+        load_json("zoom.json")
+    }
+}
+
+fn uri_host(uri: &str) -> Option<String> {
+    Url::parse(uri)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+}
+
+fn default_zoom() -> f64 {
+    Settings::load().default_zoom_percent() as f64 / 100.0
+}
+
+/// Zoom level for `uri`: the one saved for its host, or the default.
+pub fn site_zoom_level(uri: &str) -> f64 {
+    uri_host(uri)
+        .and_then(|host| SiteZoom::load().levels.get(&host).copied())
+        .unwrap_or_else(default_zoom)
+}
+
+/// Reapplies the saved zoom level after the webview navigated.
+pub fn apply_site_zoom(webview: &WebView) {
+    if let Some(uri) = webview.uri() {
+        webview.set_zoom_level(site_zoom_level(&uri));
+    }
+}
+
+/// Sets the zoom of `webview` and remembers it for the current host.
+fn set_site_zoom(webview: &WebView, level: f64) {
+    let level = level.clamp(MIN_ZOOM, MAX_ZOOM);
+    webview.set_zoom_level(level);
+
+    let host = match webview.uri().and_then(|uri| uri_host(&uri)) {
+        Some(host) => host,
+        None => return,
+    };
+
+    let mut site_zoom = SiteZoom::load();
+
+    // Sites at the default zoom don't need an entry
+    if (level - default_zoom()).abs() < f64::EPSILON {
+        site_zoom.levels.remove(&host);
+    } else {
+        site_zoom.levels.insert(host, level);
+    }
+
+    site_zoom.save();
+}
+
+pub fn change_zoom(notebook: &Notebook, step: f64) {
+    if let Some(webview) = get_webview(notebook) {
+        // Round so repeated steps don't drift away from whole percentages
+        let level = ((webview.zoom_level() + step) * 100.0).round() / 100.0;
+        set_site_zoom(&webview, level);
+    }
+}
+
+pub fn reset_zoom(notebook: &Notebook) {
+    if let Some(webview) = get_webview(notebook) {
+        set_site_zoom(&webview, default_zoom());
+    }
+}
+
+/// Shows the zoom of the current tab in the address bar, unless it is the
+/// default zoom.
+pub fn update_zoom_indicator(zoom_button: &Button, webview: Option<&WebView>) {
+    let default_level = default_zoom();
+    let level = webview.map_or(default_level, |webview| webview.zoom_level());
+
+    zoom_button.set_label(&format!("{}%", (level * 100.0).round()));
+    zoom_button.set_visible((level - default_level).abs() > f64::EPSILON);
+}
+
+/// Switches open tabs between zooming text only and whole pages.
+pub fn set_zoom_text_only(text_only: bool) {
+    for webview in all_webviews() {
+        if let Some(settings) = WebViewExt::settings(&webview) {
+            settings.set_zoom_text_only(text_only);
+        }
+    }
+}
+
+/// Keeps the window's zoom indicator in sync when `webview` is the current tab.
+pub fn connect_zoom_indicator(webview: &WebView) {
+    webview.connect_zoom_level_notify(|webview| {
+        let page = match webview.parent() {
+            Some(page) => page,
+            None => return,
+        };
+
+        if let Some(browser_window) = tab_browser_window(&page) {
+            let notebook = &browser_window.notebook;
+
+            if notebook.nth_page(notebook.current_page()).as_ref() == Some(&page) {
+                update_zoom_indicator(&browser_window.zoom_button, Some(webview));
+            }
+        }
+    });
+}